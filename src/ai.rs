@@ -0,0 +1,174 @@
+//! A computer opponent
+//!
+//! The computer looks a number of turns ahead, assuming each side plays the
+//! best turn it can find, and scores the positions it reaches by the pieces
+//! each side has left and how far their men have advanced.
+
+use crate::board::{Board, Variant, BOARD_WIDTH};
+use crate::game::Game;
+use crate::moves::Move;
+use crate::piece::Piece;
+use crate::player::Player;
+
+/// How many turns ahead the computer looks at each level of play, from the
+/// weakest
+pub const LEVELS: [u32; 5] = [1, 2, 4, 6, 8];
+
+/// How many turns ahead the computer looks at `level`, counting from 1. Levels
+/// out of range are taken as the nearest there is.
+pub fn depth(level: u32) -> u32 {
+    let i = (level.max(1) as usize - 1).min(LEVELS.len() - 1);
+    LEVELS[i]
+}
+
+const MAN: i32 = 100;
+const KING: i32 = 160;
+/// Added for every row a man has advanced
+const ADVANCE: i32 = 2;
+/// The score of a won game. Wins found sooner score higher.
+const WIN: i32 = 100_000;
+
+/// How good `board` is for `player`
+fn evaluate(board: &Board, player: Player) -> i32 {
+    let mut score = 0;
+    for (i, row) in board.board.iter().enumerate() {
+        for square in row.iter() {
+            let (owner, value) = match square {
+                Some(Piece::Normal(p)) => {
+                    // Black starts on the bottom of the board and moves up
+                    let advanced = match p {
+                        Player::Black => BOARD_WIDTH - 1 - i,
+                        Player::White => i,
+                    };
+                    (*p, MAN + advanced as i32 * ADVANCE)
+                }
+                Some(Piece::King(p)) => (*p, KING),
+                None => continue,
+            };
+            score += match owner == player {
+                true => value,
+                _ => -value,
+            };
+        }
+    }
+    score
+}
+
+/// Scores `board` for `player`, who is to move, by searching `depth` turns
+/// ahead. Lines scoring below `alpha` or above `beta` are cut short.
+fn search(
+    board: &mut Board,
+    player: Player,
+    variant: Variant,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
+    let moves = board.legal_moves_with(player, variant);
+    if moves.is_empty() {
+        return -WIN - depth as i32;
+    }
+    if depth == 0 {
+        return evaluate(board, player);
+    }
+    for m in moves.iter() {
+        board.apply(m);
+        let score = -search(board, player.switch(), variant, depth - 1, -beta, -alpha);
+        board.undo(m);
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+/// The turn the computer would play for the player to move in `game`,
+/// looking `depth` turns ahead, or `None` if they have no legal move. The
+/// game is assumed not to be part way through a turn.
+pub fn best_move(game: &Game, depth: u32) -> Option<Move> {
+    let mut board = game.board.clone();
+    let mut best = None;
+    let mut alpha = -2 * WIN;
+    for m in game.legal_moves() {
+        board.apply(&m);
+        let score = -search(
+            &mut board,
+            game.player.switch(),
+            game.variant,
+            depth.saturating_sub(1),
+            -2 * WIN,
+            -alpha,
+        );
+        board.undo(&m);
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some(m);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Position;
+
+    #[test]
+    fn keeps_its_pieces() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             ----w---\
+             --------\
+             --b-----\
+             --------\
+             --------",
+        );
+        // Moving to (4, 3) would be answered by a jump
+        let m = best_move(&g, 2).unwrap();
+        assert_eq!(m.to(), Position::new(4, 1));
+    }
+
+    #[test]
+    fn takes_the_most() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             -----w--\
+             --------\
+             -w-w----\
+             --b-----\
+             --------\
+             --------",
+        );
+        let m = best_move(&g, LEVELS[0]).unwrap();
+        assert_eq!(m.captured.len(), 2);
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(depth(0), LEVELS[0]);
+        assert_eq!(depth(2), LEVELS[1]);
+        assert_eq!(depth(99), LEVELS[LEVELS.len() - 1]);
+    }
+
+    #[test]
+    fn no_moves() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             ----w---\
+             --------\
+             --------\
+             --------\
+             --------",
+        );
+        assert_eq!(best_move(&g, 4), None);
+    }
+}
@@ -0,0 +1,14 @@
+//! Runs the Rusted Checkers client on its own, without the ncurses front-end
+
+use rustedcheckers::networking::{client, Settings};
+
+use std::process;
+
+/// Connects to the server named by the environment, or to localhost
+fn main() {
+    let result = Settings::new(None, None, None).and_then(|s| client::main(&s));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,15 @@
+//! Runs the Rusted Checkers server on its own, without the ncurses front-end
+
+use rustedcheckers::networking::server::{self, Config};
+use rustedcheckers::networking::Settings;
+
+use std::process;
+
+/// Listens where the environment says to, or on every interface
+fn main() {
+    let result = Settings::new(None, None, None).and_then(|s| server::main(&s, Config::default()));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -1,26 +1,83 @@
 //! Board
 
-use crate::moves::Submove;
+use crate::moves::{Move, Moves, Submove};
 use crate::piece::Piece;
 use crate::player::Player;
 
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
 use std::fmt;
 use std::ops::{Add, Sub};
 
 /// An American checkers game has a board width of 8.
 pub const BOARD_WIDTH: usize = 8;
 
+/// The four diagonal (row, column) directions a piece may travel in
+const DIAGONALS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
 /// A `Point` can either be empty or contain a `Piece`
 pub type Point = Option<Piece>;
 
+/// Rule variants which change how far a piece may travel in a single submove
+///
+/// Serialized as the name of the variant, e.g. `"American"`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Variant {
+    /// American checkers, in which every piece moves a single square
+    American,
+    /// Kings may travel any distance along an open diagonal
+    FlyingKings,
+}
+
 /// An (x, y) coordinate representation of a position on the board
-#[derive(Clone, Copy, Debug, PartialEq)]
+///
+/// Serialized as a two element sequence of row and column, e.g. `[5, 0]`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Position(pub usize, pub usize);
 
 impl Position {
     pub fn new(x: usize, y: usize) -> Self {
         Position(x, y)
     }
+
+    /// Returns the position as seen from the [`Player`](../player/enum.Player.html)'s
+    /// perspective. This mirrors [`Board::norm()`](./struct.Board.html#method.norm), and
+    /// normalizing twice returns the original position.
+    pub fn norm(&self, p: Player) -> Position {
+        match p {
+            Player::White => Position(BOARD_WIDTH - 1 - self.0, BOARD_WIDTH - 1 - self.1),
+            _ => *self,
+        }
+    }
+
+    /// Whether the position lies within the board
+    pub fn on_board(&self) -> bool {
+        self.0 < BOARD_WIDTH && self.1 < BOARD_WIDTH
+    }
+
+    /// Returns the standard notation square number (1 through 32) of a playable
+    /// position, or `None` for the unplayable squares.
+    ///
+    /// Squares are numbered from Black's side of the board: square 1 is at
+    /// (7,6), the corner of Black's home row, so Black's starting pieces
+    /// occupy squares 1 through 12 and White's occupy 21 through 32.
+    pub fn square(&self) -> Option<usize> {
+        if !self.on_board() || (self.0 + self.1).is_multiple_of(2) {
+            return None;
+        }
+        let (row, col) = (BOARD_WIDTH - 1 - self.0, BOARD_WIDTH - 1 - self.1);
+        Some(row * BOARD_WIDTH / 2 + col / 2 + 1)
+    }
+
+    /// The inverse of [`Position::square()`](#method.square)
+    pub fn from_square(n: usize) -> Option<Position> {
+        if !(1..=BOARD_WIDTH * BOARD_WIDTH / 2).contains(&n) {
+            return None;
+        }
+        let row = (n - 1) / (BOARD_WIDTH / 2);
+        let col = (n - 1) % (BOARD_WIDTH / 2) * 2 + (row + 1) % 2;
+        Some(Position(BOARD_WIDTH - 1 - row, BOARD_WIDTH - 1 - col))
+    }
 }
 
 impl Sub for Position {
@@ -94,6 +151,29 @@ impl Board {
         }
     }
 
+    /// Parses a board in the format accepted by `Board::from(&str)`, returning
+    /// an `Err(_)` rather than panicking if it is malformed.
+    pub fn parse(s: &str) -> Result<Board, String> {
+        let len = s.chars().count();
+        if len != BOARD_WIDTH * BOARD_WIDTH {
+            return Err(format!(
+                "A board must have {} squares, not {}.",
+                BOARD_WIDTH * BOARD_WIDTH,
+                len
+            ));
+        }
+        match s.chars().find(|c| !"wWbB-·".contains(*c)) {
+            Some(c) => Err(format!("Character '{}' is invalid.", c)),
+            None => Ok(Board::from(s)),
+        }
+    }
+
+    /// The board as a single line, in the format accepted by
+    /// [`Board::parse()`](#method.parse)
+    pub fn compact(&self) -> String {
+        self.to_string().replace('·', "-").replace('\n', "")
+    }
+
     /// Returns a board that is normalized from the
     /// [`Player`](../player/enum.Player.html)'s perspective.
     /// XXX: I'm not sure if this is changing the board in the right match or
@@ -152,36 +232,61 @@ impl Board {
     /// TODO: This should be moved back into `Board` and replaced by a wrapper
     /// function.
     ///
+    /// Validate a submove under the rules of American checkers. See
+    /// [`Board::validate_submove_with()`](./struct.Board.html#method.validate_submove_with).
+    pub fn validate_submove(&self, s: &Submove, player: Player) -> Result<bool, String> {
+        self.validate_submove_with(s, player, Variant::American)
+    }
+
     /// Validate a submove. This returns either `Ok(true)` or the first
     /// `Err(_)` to have occurred. Checking happens in the following manner.
     ///
     /// Each step of the validaiton is a closure so as to defer execution until a later time.
     ///
     /// Check that:
-    /// 1. The piece being moved exists
-    /// 2. The destination is empty
-    /// 3. The piece being moved is owned by the player
-    /// 4. The piece is moving forward if it is not kinged
-    pub fn validate_submove(&self, s: &Submove, player: Player) -> Result<bool, String> {
-        let piece_exists = |x: &Position| match self.norm(player).at(x).is_some() {
+    /// 1. Both positions are on the board
+    /// 2. The piece being moved exists
+    /// 3. The destination is empty
+    /// 4. The piece being moved is owned by the player
+    /// 5. The piece is moving forward if it is not kinged
+    /// 6. The piece is moving diagonally
+    /// 7. The piece is moving a single square, or jumping a single opponent
+    ///    piece onto the square directly behind it. Kings of a
+    ///    [`Variant::FlyingKings`](./enum.Variant.html) game may travel any
+    ///    distance along an open diagonal and land anywhere behind the piece
+    ///    they jump.
+    pub fn validate_submove_with(
+        &self,
+        s: &Submove,
+        player: Player,
+        variant: Variant,
+    ) -> Result<bool, String> {
+        let board = self.norm(player);
+
+        let on_board = |x: &Position| match x.0 < BOARD_WIDTH && x.1 < BOARD_WIDTH {
+            false => Err(format!("Position {} is not on the board.", x)),
+            _ => Ok(true),
+        };
+
+        let piece_exists = |x: &Position| match board.at(x).is_some() {
             false => Err(format!("No piece exists at {}.", x)),
             _ => Ok(true),
         };
 
-        let dest_empty = |x: &Position| match self.norm(player).at(x).is_none() {
+        let dest_empty = |x: &Position| match board.at(x).is_none() {
             false => Err(format!("Position {} is not empty.", x)),
             _ => Ok(true),
         };
 
         // `unwrap()` is safe because we know that the piece exists
-        let owns_piece = |x: &Position| match self.norm(player).at(x).unwrap() {
+        let owns_piece = |x: &Position| match board.at(x).unwrap() {
             Piece::Normal(v) | Piece::King(v) => match v == player {
                 false => Err(format!("Player {:?} does not own piece at {}.", player, x)),
                 _ => Ok(true),
             },
         };
 
-        let moving_forward = |s: &Submove| match self.norm(player).at(&s.from).unwrap() {
+        let moving_forward = |s: &Submove| match board.at(&s.from).unwrap() {
             Piece::Normal(_) => match s.from.0 > s.to.0 {
                 false => Err(format!(
                     "Normal pieces must move forward: {} -> {}",
@@ -192,12 +297,69 @@ impl Board {
             _ => Ok(true),
         };
 
+        let diagonal = |s: &Submove| {
+            let (dx, dy) = s.delta();
+            match dx.abs() == dy.abs() {
+                false => Err(format!(
+                    "Pieces must move diagonally: {} -> {}",
+                    s.from, s.to
+                )),
+                _ => Ok(true),
+            }
+        };
+
+        let distance = |s: &Submove| {
+            let (dx, dy) = s.delta();
+            let flying = match board.at(&s.from).unwrap() {
+                Piece::King(_) => variant == Variant::FlyingKings,
+                _ => false,
+            };
+
+            // Pieces lying strictly between the origin and destination
+            let between: Vec<(Position, Piece)> = (1..dx.abs())
+                .map(|i| {
+                    Position::from((
+                        s.from.0 as i32 + dx.signum() * i,
+                        s.from.1 as i32 + dy.signum() * i,
+                    ))
+                })
+                .filter_map(|p| board.at(&p).map(|x| (p, x)))
+                .collect();
+
+            if dx.abs() > 2 && !flying {
+                return Err(format!(
+                    "Pieces may only move one square or jump one piece: {} -> {}",
+                    s.from, s.to
+                ));
+            }
+
+            match between.as_slice() {
+                [] if dx.abs() == 2 && !flying => Err(format!(
+                    "There is no piece to jump over: {} -> {}",
+                    s.from, s.to
+                )),
+                [] => Ok(true),
+                [(p, x)] => match x.player() == player {
+                    true => Err(format!("Pieces may not jump their own piece at {}.", p)),
+                    _ => Ok(true),
+                },
+                _ => Err(format!(
+                    "Pieces may only jump one piece at a time: {} -> {}",
+                    s.from, s.to
+                )),
+            }
+        };
+
         // Reduce to `Ok(true)` or the first `Err`
-        Ok(vec![
+        Ok([
+            on_board(&s.from)?,
+            on_board(&s.to)?,
             piece_exists(&s.from)?,
             dest_empty(&s.to)?,
             owns_piece(&s.from)?,
             moving_forward(s)?,
+            diagonal(s)?,
+            distance(s)?,
         ]
         .iter()
         .all(|x| *x))
@@ -209,6 +371,268 @@ impl Board {
         self.remove(&s.to);
         self.place(piece, &s.to);
     }
+
+    /// Returns the position of the first piece lying strictly between the
+    /// ends of a submove, if any.
+    pub fn jumped(&self, s: &Submove) -> Option<Position> {
+        let (dx, dy) = s.delta();
+        (1..dx.abs())
+            .map(|i| {
+                Position::from((
+                    s.from.0 as i32 + dx.signum() * i,
+                    s.from.1 as i32 + dy.signum() * i,
+                ))
+            })
+            .find(|p| self.at(p).is_some())
+    }
+
+    /// Lists every valid submove of the piece at `p`. Unlike
+    /// [`Board::validate_submove()`](#method.validate_submove), positions are
+    /// given as they appear on the board rather than normalized for `player`.
+    pub fn submoves_from(&self, p: Position, player: Player, variant: Variant) -> Vec<Submove> {
+        let mut submoves = Vec::new();
+        for (x, y) in DIAGONALS.iter() {
+            for i in 1..BOARD_WIDTH as i32 {
+                let to = Position::from((p.0 as i32 + x * i, p.1 as i32 + y * i));
+                if !to.on_board() {
+                    break;
+                }
+                let s = Submove { from: p, to };
+                if self
+                    .validate_submove_with(&s.norm(player), player, variant)
+                    .is_ok()
+                {
+                    submoves.push(s);
+                }
+            }
+        }
+        submoves
+    }
+
+    /// Lists the valid jumps of the piece at `p`
+    pub fn captures_from(&self, p: Position, player: Player, variant: Variant) -> Vec<Submove> {
+        self.submoves_from(p, player, variant)
+            .into_iter()
+            .filter(|s| self.jumped(s).is_some())
+            .collect()
+    }
+
+    /// Whether `player` has any jump available anywhere on the board
+    pub fn can_capture(&self, player: Player, variant: Variant) -> bool {
+        self.pieces(player)
+            .iter()
+            .any(|p| !self.captures_from(*p, player, variant).is_empty())
+    }
+
+    /// Positions of every piece owned by `player`
+    pub fn pieces(&self, player: Player) -> Vec<Position> {
+        let mut pieces = Vec::new();
+        for (i, row) in self.board.iter().enumerate() {
+            for (j, x) in row.iter().enumerate() {
+                if x.map(|x| x.player()) == Some(player) {
+                    pieces.push(Position::new(i, j));
+                }
+            }
+        }
+        pieces
+    }
+
+    /// Performs a single submove, removing any jumped piece and crowning a
+    /// piece which reaches the far side of the board. The captured piece and
+    /// whether the moving piece was crowned are returned.
+    pub fn step(&mut self, s: &Submove, player: Player) -> (Option<(Position, Piece)>, bool) {
+        let captured = self.jumped(s).map(|p| (p, self.at(&p).unwrap()));
+        if let Some((p, _)) = captured {
+            self.remove(&p);
+        }
+        self.mutate(s);
+
+        let crowned = match self.at(&s.to) {
+            Some(Piece::Normal(v)) if s.to.norm(player).0 == 0 => {
+                self.place(Piece::King(v), &s.to);
+                true
+            }
+            _ => false,
+        };
+        (captured, crowned)
+    }
+
+    /// Completes every jump sequence continuing on from the partial move `m`
+    fn capture_sequences(&self, m: Move, player: Player, variant: Variant, out: &mut Moves) {
+        let captures = match m.promoted {
+            // Crowning a piece ends the turn
+            true => vec![],
+            _ => self.captures_from(m.to(), player, variant),
+        };
+        if captures.is_empty() {
+            if !m.path.is_empty() {
+                out.push(m);
+            }
+            return;
+        }
+        for s in captures.iter() {
+            let mut board = self.clone();
+            let (captured, promoted) = board.step(s, player);
+            let mut next = m.clone();
+            next.path.push(s.to);
+            next.captured.extend(captured);
+            next.promoted = promoted;
+            board.capture_sequences(next, player, variant, out);
+        }
+    }
+
+    /// Lists every legal turn for `player` under the rules of American
+    /// checkers. See [`Board::legal_moves_with()`](#method.legal_moves_with).
+    pub fn legal_moves(&self, player: Player) -> Moves {
+        self.legal_moves_with(player, Variant::American)
+    }
+
+    /// Lists every legal turn for `player`. Capturing is mandatory, so if any
+    /// jump is available only complete jump sequences are returned.
+    pub fn legal_moves_with(&self, player: Player, variant: Variant) -> Moves {
+        let mut moves = Vec::new();
+        if self.can_capture(player, variant) {
+            for p in self.pieces(player) {
+                self.capture_sequences(Move::new(p), player, variant, &mut moves);
+            }
+        } else {
+            for p in self.pieces(player) {
+                for s in self.submoves_from(p, player, variant) {
+                    let mut board = self.clone();
+                    let (_, promoted) = board.step(&s, player);
+                    let mut m = Move::new(p);
+                    m.path.push(s.to);
+                    m.promoted = promoted;
+                    moves.push(m);
+                }
+            }
+        }
+        moves
+    }
+
+    /// Builds the turn in which `player` moves the piece at `from` along
+    /// `path`, filling in captured pieces and promotion. This returns the
+    /// first rule to have been broken as an `Err(_)`.
+    pub fn build_move(
+        &self,
+        from: Position,
+        path: &[Position],
+        player: Player,
+        variant: Variant,
+    ) -> Result<Move, String> {
+        if path.is_empty() {
+            return Err(String::from("A move must travel at least one square."));
+        }
+
+        let mut board = self.clone();
+        let mut m = Move::new(from);
+        for &to in path.iter() {
+            let s = Submove { from: m.to(), to };
+            if m.promoted {
+                return Err(format!(
+                    "A crowned piece ends its turn: {} -> {}",
+                    s.from, s.to
+                ));
+            }
+            board.validate_submove_with(&s.norm(player), player, variant)?;
+            match board.jumped(&s) {
+                None if !m.path.is_empty() => {
+                    return Err(format!(
+                        "Only jumps may follow a jump: {} -> {}",
+                        s.from, s.to
+                    ))
+                }
+                None if self.can_capture(player, variant) => {
+                    return Err(String::from("A capture is available and must be taken."))
+                }
+                _ => (),
+            }
+            let (captured, promoted) = board.step(&s, player);
+            m.path.push(to);
+            m.captured.extend(captured);
+            m.promoted = promoted;
+        }
+
+        if !m.captured.is_empty()
+            && !m.promoted
+            && !board.captures_from(m.to(), player, variant).is_empty()
+        {
+            return Err(format!("The jump from {} must be continued.", m.to()));
+        }
+        Ok(m)
+    }
+
+    /// Validate a complete turn under the rules of American checkers. See
+    /// [`Board::validate_move_with()`](#method.validate_move_with).
+    pub fn validate_move(&self, m: &Move, player: Player) -> Result<bool, String> {
+        self.validate_move_with(m, player, Variant::American)
+    }
+
+    /// Validate a complete turn. Every submove must be valid, captures must
+    /// be taken and jump sequences completed, and the captured pieces and
+    /// promotion recorded in the move must match the board.
+    pub fn validate_move_with(
+        &self,
+        m: &Move,
+        player: Player,
+        variant: Variant,
+    ) -> Result<bool, String> {
+        match self.build_move(m.from, &m.path, player, variant)? == *m {
+            false => Err(format!("Move {} does not match the board.", m)),
+            _ => Ok(true),
+        }
+    }
+
+    /// Plays a complete turn. The move is assumed to be valid.
+    pub fn apply(&mut self, m: &Move) {
+        let piece = self.at(&m.from).unwrap();
+        self.remove(&m.from);
+        for (p, _) in m.captured.iter() {
+            self.remove(p);
+        }
+        let piece = match m.promoted {
+            true => Piece::King(piece.player()),
+            _ => piece,
+        };
+        self.place(piece, &m.to());
+    }
+
+    /// Takes back a turn previously played with
+    /// [`Board::apply()`](#method.apply).
+    pub fn undo(&mut self, m: &Move) {
+        let piece = self.at(&m.to()).unwrap();
+        self.remove(&m.to());
+        for (p, x) in m.captured.iter() {
+            self.place(*x, p);
+        }
+        let piece = match m.promoted {
+            true => Piece::Normal(piece.player()),
+            _ => piece,
+        };
+        self.place(piece, &m.from);
+    }
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+/// A board is serialized as the 64 character string accepted by
+/// [`Board::parse()`](./struct.Board.html#method.parse), listing each row from
+/// the top of the board.
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.compact())
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Board::parse(&s).map_err(de::Error::custom)
+    }
 }
 
 /// A board can be specified by a series 'b', 'w', and '-' to specify black, white, and empty
@@ -220,8 +644,10 @@ impl From<&str> for Board {
             let x = i / 8;
             let y = i % 8;
             match c {
-                'w' | 'W' => board[x][y] = Some(Piece::white()),
-                'b' | 'B' => board[x][y] = Some(Piece::black()),
+                'w' => board[x][y] = Some(Piece::white()),
+                'b' => board[x][y] = Some(Piece::black()),
+                'W' => board[x][y] = Some(Piece::white_king()),
+                'B' => board[x][y] = Some(Piece::black_king()),
                 '-' | '·' => (),
                 _ => panic!("Character '{}' is invalid.", c),
             }
@@ -275,7 +701,7 @@ mod tests {
     #[should_panic]
     fn from_panic() {
         let s = "x";
-        Board::from(s);
+        let _ = Board::from(s);
     }
 
     #[test]
@@ -325,6 +751,301 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_submove_off_board() {
+        let b = Board::new();
+        assert_eq!(
+            b.validate_submove(&Submove::new((5, 0), (4, 8)), Player::Black),
+            Err(String::from("Position (4, 8) is not on the board."))
+        );
+    }
+
+    #[test]
+    fn validate_submove_diagonal() {
+        let b = Board::new();
+        assert_eq!(
+            b.validate_submove(&Submove::new((5, 0), (4, 0)), Player::Black),
+            Err(String::from(
+                "Pieces must move diagonally: (5, 0) -> (4, 0)"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_submove_distance() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             --------\
+             --b-----\
+             --------\
+             --------",
+        );
+        assert_eq!(
+            b.validate_submove(&Submove::new((5, 2), (2, 5)), Player::Black),
+            Err(String::from(
+                "Pieces may only move one square or jump one piece: (5, 2) -> (2, 5)"
+            ))
+        );
+        let b = Board::new();
+        assert_eq!(
+            b.validate_submove(&Submove::new((5, 2), (3, 4)), Player::Black),
+            Err(String::from(
+                "There is no piece to jump over: (5, 2) -> (3, 4)"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_submove_jump() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             ---w----\
+             ----b---\
+             -----b--\
+             --------",
+        );
+        assert_eq!(
+            b.validate_submove(&Submove::new((5, 4), (3, 2)), Player::Black),
+            Ok(true)
+        );
+        assert_eq!(
+            b.validate_submove(&Submove::new((6, 5), (4, 3)), Player::Black),
+            Err(String::from("Position (4, 3) is not empty."))
+        );
+        assert_eq!(
+            b.validate_submove(&Submove::new((6, 5), (3, 2)), Player::Black),
+            Err(String::from(
+                "Pieces may only move one square or jump one piece: (6, 5) -> (3, 2)"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_submove_jump_own() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             --------\
+             ----b---\
+             -----b--\
+             --------",
+        );
+        assert_eq!(
+            b.validate_submove(&Submove::new((6, 5), (4, 3)), Player::Black),
+            Err(String::from(
+                "Pieces may not jump their own piece at (5, 4)."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_submove_flying_kings() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             --------\
+             --------\
+             -w------\
+             B-------",
+        );
+        let s = Submove::new((7, 0), (2, 5));
+        assert!(b.validate_submove(&s, Player::Black).is_err());
+        assert_eq!(
+            b.validate_submove_with(&s, Player::Black, Variant::FlyingKings),
+            Ok(true)
+        );
+
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             ---w----\
+             --------\
+             -w------\
+             B-------",
+        );
+        assert_eq!(
+            b.validate_submove_with(&s, Player::Black, Variant::FlyingKings),
+            Err(String::from(
+                "Pieces may only jump one piece at a time: (7, 0) -> (2, 5)"
+            ))
+        );
+    }
+
+    #[test]
+    fn position_square() {
+        assert_eq!(Position::new(7, 6).square(), Some(1));
+        assert_eq!(Position::new(5, 2).square(), Some(11));
+        assert_eq!(Position::new(0, 1).square(), Some(32));
+        assert_eq!(Position::new(0, 0).square(), None);
+        for n in 1..33 {
+            assert_eq!(Position::from_square(n).unwrap().square(), Some(n));
+        }
+        assert_eq!(Position::from_square(33), None);
+    }
+
+    #[test]
+    fn legal_moves_new() {
+        let b = Board::new();
+        assert_eq!(b.legal_moves(Player::Black).len(), 7);
+        assert_eq!(b.legal_moves(Player::White).len(), 7);
+    }
+
+    #[test]
+    fn legal_moves_capture() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --w-w---\
+             --------\
+             --w-----\
+             -b-b----\
+             --------",
+        );
+        let moves: Vec<String> = b
+            .legal_moves(Player::Black)
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(moves, vec!["8x15x24", "8x15x22", "7x16x23"]);
+    }
+
+    #[test]
+    fn legal_moves_promotion() {
+        let b = Board::from(
+            "--------\
+             --w-----\
+             ---b----\
+             --------\
+             --------\
+             --------\
+             --------\
+             --------",
+        );
+        let moves = b.legal_moves(Player::Black);
+        assert_eq!(moves.len(), 1);
+        assert!(moves[0].promoted);
+        assert_eq!(moves[0].path, vec![Position::new(0, 1)]);
+    }
+
+    #[test]
+    fn build_move_incomplete() {
+        let b = Board::from(
+            "--------\
+             --------\
+             --------\
+             --w-----\
+             --------\
+             --w-----\
+             -b------\
+             --------",
+        );
+        assert_eq!(
+            b.build_move(
+                Position::new(6, 1),
+                &[Position::new(4, 3)],
+                Player::Black,
+                Variant::American
+            ),
+            Err(String::from("The jump from (4, 3) must be continued."))
+        );
+    }
+
+    #[test]
+    fn validate_move_mismatch() {
+        let b = Board::new();
+        let mut m = Move::new((5, 0));
+        m.path.push(Position::new(4, 1));
+        assert_eq!(b.validate_move(&m, Player::Black), Ok(true));
+        m.promoted = true;
+        assert_eq!(
+            b.validate_move(&m, Player::Black),
+            Err(String::from("Move 12-16 does not match the board."))
+        );
+    }
+
+    #[test]
+    fn apply_undo() {
+        let b = Board::from(
+            "--------\
+             --w-----\
+             ---b----\
+             --------\
+             --------\
+             --------\
+             --------\
+             --------",
+        );
+        let m = b.legal_moves(Player::Black).remove(0);
+        let mut after = b.clone();
+        after.apply(&m);
+        assert_eq!(after.at(&Position::new(0, 1)), Some(Piece::black_king()));
+        assert_eq!(after.count_pieces(Player::White), 0);
+        after.undo(&m);
+        assert_eq!(after, b);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Board::parse("x"),
+            Err(String::from("A board must have 64 squares, not 1."))
+        );
+        assert_eq!(
+            Board::parse(&"x".repeat(64)),
+            Err(String::from("Character 'x' is invalid."))
+        );
+        assert_eq!(Board::parse(&Board::new().compact()), Ok(Board::new()));
+    }
+
+    #[test]
+    fn serialize_json() {
+        let b = Board::from(
+            "-w-w-w-w\
+             w-w-w-w-\
+             -w-w-w-w\
+             --------\
+             --------\
+             B-b-b-b-\
+             -b-b-b-b\
+             b-b-b-b-",
+        );
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(
+            json,
+            "\"-w-w-w-ww-w-w-w--w-w-w-w----------------B-b-b-b--b-b-b-bb-b-b-b-\""
+        );
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), b);
+        assert!(serde_json::from_str::<Board>("\"-w-w\"").is_err());
+        assert_eq!(
+            serde_json::to_string(&Position::new(5, 0)).unwrap(),
+            "[5,0]"
+        );
+        assert_eq!(
+            serde_json::to_string(&Variant::FlyingKings).unwrap(),
+            "\"FlyingKings\""
+        );
+    }
+
+    #[test]
+    fn serialize_bincode() {
+        let b = Board::new();
+        let bytes = bincode::serialize(&b).unwrap();
+        assert_eq!(bincode::deserialize::<Board>(&bytes).unwrap(), b);
+    }
+
     #[test]
     fn display() {
         let b = Board::new();
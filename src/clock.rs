@@ -0,0 +1,314 @@
+//! Game clocks, and the time controls they keep
+//!
+//! A [`Clock`](./struct.Clock.html) only keeps the time each player had left
+//! at the start of the current turn. It does not measure time itself: whoever
+//! runs the game says how long each turn took when it ends, which lets the
+//! same clock be kept by a server, by both peers of a game, or by a single
+//! terminal.
+
+use crate::player::Player;
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How much time each player is given
+///
+/// Written as the minutes each player starts with, followed by what they are
+/// given back: `5` is five minutes for the game, `5+3` adds three seconds
+/// after every turn, `5d3` gives back up to three seconds of every turn, and
+/// `40/90` is ninety minutes for every forty turns.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TimeControl {
+    /// A fixed amount of time for the whole game
+    SuddenDeath(Duration),
+    /// An amount of time, and an increment added after every turn
+    Fischer { base: Duration, increment: Duration },
+    /// An amount of time, and a delay. After every turn the time it took is
+    /// given back, up to the delay.
+    Bronstein { base: Duration, delay: Duration },
+    /// An amount of time for a number of turns, given again each time that
+    /// many turns have been played. Unused time carries over.
+    Periods { moves: u32, period: Duration },
+}
+
+impl TimeControl {
+    /// The time each player starts with
+    pub fn base(&self) -> Duration {
+        match *self {
+            TimeControl::SuddenDeath(base) => base,
+            TimeControl::Fischer { base, .. } => base,
+            TimeControl::Bronstein { base, .. } => base,
+            TimeControl::Periods { period, .. } => period,
+        }
+    }
+}
+
+fn minutes(d: Duration) -> u64 {
+    d.as_secs() / 60
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeControl::SuddenDeath(base) => write!(f, "{}", minutes(base)),
+            TimeControl::Fischer { base, increment } => {
+                write!(f, "{}+{}", minutes(base), increment.as_secs())
+            }
+            TimeControl::Bronstein { base, delay } => {
+                write!(f, "{}d{}", minutes(base), delay.as_secs())
+            }
+            TimeControl::Periods { moves, period } => write!(f, "{}/{}", moves, minutes(period)),
+        }
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TimeControl, String> {
+        let err = || format!("'{}' is not a time control; try 5, 5+3, 5d3 or 40/90.", s);
+        let number = |n: &str| n.trim().parse::<u64>().map_err(|_| err());
+        let mins = |n: &str| {
+            number(n)?
+                .checked_mul(60)
+                .map(Duration::from_secs)
+                .ok_or_else(err)
+        };
+        let secs = |n: &str| number(n).map(Duration::from_secs);
+
+        let control = if let Some(i) = s.find('+') {
+            TimeControl::Fischer {
+                base: mins(&s[..i])?,
+                increment: secs(&s[i + 1..])?,
+            }
+        } else if let Some(i) = s.find('d') {
+            TimeControl::Bronstein {
+                base: mins(&s[..i])?,
+                delay: secs(&s[i + 1..])?,
+            }
+        } else if let Some(i) = s.find('/') {
+            TimeControl::Periods {
+                moves: s[..i].trim().parse::<u32>().map_err(|_| err())?,
+                period: mins(&s[i + 1..])?,
+            }
+        } else {
+            TimeControl::SuddenDeath(mins(s)?)
+        };
+        match control {
+            TimeControl::Periods { moves: 0, .. } => Err(err()),
+            c if c.base() == Duration::from_secs(0) => Err(err()),
+            c => Ok(c),
+        }
+    }
+}
+
+/// The time each player has left, kept to a time control
+///
+/// Serialized as a map of its fields, with each duration given as its
+/// seconds and nanoseconds, e.g.
+/// `{"control":{"SuddenDeath":{"secs":300,"nanos":0}},"black":{"secs":300,"nanos":0},...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    pub control: TimeControl,
+    black: Duration,
+    white: Duration,
+    /// How many turns each player has finished, black's first
+    turns: (u32, u32),
+    /// How long the player to move had been thinking when the clock was
+    /// read, for a clock sent part way through a turn
+    pub elapsed: Duration,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            black: control.base(),
+            white: control.base(),
+            turns: (0, 0),
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    fn remaining(&mut self, p: Player) -> &mut Duration {
+        match p {
+            Player::Black => &mut self.black,
+            Player::White => &mut self.white,
+        }
+    }
+
+    /// The time `p` has left, with `to_move` having been thinking for
+    /// `thinking` since the clock was read
+    pub fn left(&self, p: Player, to_move: Player, thinking: Duration) -> Duration {
+        let remaining = match p {
+            Player::Black => self.black,
+            Player::White => self.white,
+        };
+        if p != to_move {
+            return remaining;
+        }
+        remaining
+            .checked_sub(self.elapsed + thinking)
+            .unwrap_or_else(|| Duration::from_secs(0))
+    }
+
+    /// Stops `p`'s clock at the end of a turn they have been thinking about
+    /// for `thinking` since the clock was read, and gives them whatever the
+    /// time control gives back. Returns `false`, leaving them no time, if
+    /// their time ran out before the turn was finished.
+    pub fn press(&mut self, p: Player, thinking: Duration) -> bool {
+        let used = self.elapsed + thinking;
+        self.elapsed = Duration::from_secs(0);
+        let control = self.control;
+        let turns = match p {
+            Player::Black => &mut self.turns.0,
+            Player::White => &mut self.turns.1,
+        };
+        *turns += 1;
+        let turns = *turns;
+
+        let remaining = self.remaining(p);
+        match remaining.checked_sub(used) {
+            Some(left) if left > Duration::from_secs(0) => *remaining = left,
+            _ => {
+                *remaining = Duration::from_secs(0);
+                return false;
+            }
+        }
+        match control {
+            TimeControl::SuddenDeath(_) => (),
+            TimeControl::Fischer { increment, .. } => *remaining += increment,
+            TimeControl::Bronstein { delay, .. } => *remaining += used.min(delay),
+            TimeControl::Periods { moves, period } => {
+                if turns % moves == 0 {
+                    *remaining += period;
+                }
+            }
+        }
+        true
+    }
+
+    /// A copy of the clock for sending to someone else, with the player to
+    /// move having been thinking for `thinking` since it was read
+    pub fn read(&self, thinking: Duration) -> Clock {
+        let mut clock = self.clone();
+        clock.elapsed += thinking;
+        clock
+    }
+}
+
+/// Shows time left on a clock, e.g. `4:05`, or `1:02:03` if it is an hour or
+/// more. The last ten seconds are shown in tenths, e.g. `0:09.4`.
+pub fn format_time(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("0:{:02}.{}", secs, d.subsec_millis() / 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("5".parse(), Ok(TimeControl::SuddenDeath(secs(300))));
+        assert_eq!(
+            "5+3".parse(),
+            Ok(TimeControl::Fischer {
+                base: secs(300),
+                increment: secs(3),
+            })
+        );
+        assert_eq!(
+            "5d3".parse(),
+            Ok(TimeControl::Bronstein {
+                base: secs(300),
+                delay: secs(3),
+            })
+        );
+        assert_eq!(
+            "40/90".parse(),
+            Ok(TimeControl::Periods {
+                moves: 40,
+                period: secs(90 * 60),
+            })
+        );
+        assert!("five".parse::<TimeControl>().is_err());
+        assert!("0".parse::<TimeControl>().is_err());
+        assert!("0/90".parse::<TimeControl>().is_err());
+        assert!("307445734561825861".parse::<TimeControl>().is_err());
+        assert!("4294967336/90".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        for s in ["5", "5+3", "5d3", "40/90"].iter() {
+            assert_eq!(s.parse::<TimeControl>().unwrap().to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn sudden_death() {
+        let mut clock = Clock::new(TimeControl::SuddenDeath(secs(60)));
+        assert_eq!(clock.left(Player::Black, Player::Black, secs(20)), secs(40));
+        assert_eq!(clock.left(Player::White, Player::Black, secs(20)), secs(60));
+        assert!(clock.press(Player::Black, secs(20)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(40));
+        assert!(!clock.press(Player::White, secs(60)));
+        assert_eq!(clock.left(Player::White, Player::Black, secs(0)), secs(0));
+    }
+
+    #[test]
+    fn fischer() {
+        let mut clock = Clock::new("1+5".parse().unwrap());
+        assert!(clock.press(Player::Black, secs(2)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(63));
+    }
+
+    #[test]
+    fn bronstein() {
+        let mut clock = Clock::new("1d5".parse().unwrap());
+        assert!(clock.press(Player::Black, secs(2)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(60));
+        assert!(clock.press(Player::Black, secs(8)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(57));
+        // The delay is no use once the time has run out
+        assert!(!clock.press(Player::Black, secs(57)));
+    }
+
+    #[test]
+    fn periods() {
+        let mut clock = Clock::new("2/1".parse().unwrap());
+        assert!(clock.press(Player::Black, secs(10)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(50));
+        assert!(clock.press(Player::Black, secs(10)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(100));
+    }
+
+    #[test]
+    fn read() {
+        let clock = Clock::new(TimeControl::SuddenDeath(secs(60))).read(secs(15));
+        assert_eq!(clock.left(Player::Black, Player::Black, secs(5)), secs(40));
+        let mut clock = clock;
+        assert!(clock.press(Player::Black, secs(5)));
+        assert_eq!(clock.left(Player::Black, Player::White, secs(0)), secs(40));
+        assert_eq!(clock.elapsed, secs(0));
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_time(secs(245)), "4:05");
+        assert_eq!(format_time(secs(3723)), "1:02:03");
+        assert_eq!(format_time(Duration::from_millis(9_450)), "0:09.4");
+    }
+}
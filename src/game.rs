@@ -1,19 +1,37 @@
 //! Management of game state
 
-use crate::board::{Board, Position, BOARD_WIDTH};
-use crate::moves::Submove;
-use crate::piece::Piece;
+use crate::board::{Board, Position, Variant};
+use crate::clock::Clock;
+use crate::moves::{self, Move, Moves, Submove};
 use crate::player::Player;
 
+use std::mem;
+use std::time::Duration;
+
 pub const NAME: &str = "Rusted Checkers";
 
-#[derive(Debug, PartialEq)]
+/// Serialized as a map of its fields. The selection and highlighting are
+/// interface state and are not serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Game {
     pub board: Board,
     pub player: Player,
+    pub variant: Variant,
+    #[serde(skip)]
     pub selected: Option<Position>,
+    #[serde(skip)]
     pub hilighted: Vec<Position>,
-    pub nmoves: u8,
+    /// The turn currently being played one submove at a time, if a piece is
+    /// part way through a jump sequence
+    pub turn: Option<Move>,
+    /// Every completed turn, oldest first
+    pub history: Moves,
+    pub black_name: String,
+    pub white_name: String,
+    /// The players' clocks, if the game is timed. Games saved before clocks
+    /// were kept load untimed.
+    #[serde(default)]
+    pub clock: Option<Clock>,
 }
 
 impl Game {
@@ -22,96 +40,138 @@ impl Game {
         Game {
             board: Board::new(),
             player: Player::Black,
+            variant: Variant::American,
             selected: None,
             hilighted: Vec::new(),
-            nmoves: 1,
+            turn: None,
+            history: Vec::new(),
+            black_name: String::from("Black"),
+            white_name: String::from("White"),
+            clock: None,
+        }
+    }
+
+    /// The name of the person playing as `p`
+    pub fn name(&self, p: Player) -> &str {
+        match p {
+            Player::Black => &self.black_name,
+            Player::White => &self.white_name,
+        }
+    }
+
+    /// A new game between the same players, with the same variant and time
+    /// control. If `swap`, each plays the colour the other had.
+    pub fn rematch(&self, swap: bool) -> Game {
+        let mut game = Game::new();
+        game.variant = self.variant;
+        game.black_name = self.black_name.clone();
+        game.white_name = self.white_name.clone();
+        if swap {
+            mem::swap(&mut game.black_name, &mut game.white_name);
         }
+        game.clock = self.clock.as_ref().map(|c| Clock::new(c.control));
+        game
+    }
+
+    /// The time `p` has left in a timed game, with the player to move having
+    /// been thinking for `thinking` since the clock was read
+    pub fn time_left(&self, p: Player, thinking: Duration) -> Option<Duration> {
+        self.clock
+            .as_ref()
+            .map(|c| c.left(p, self.player, thinking))
+    }
+
+    /// Whether the player to move has run out of time, having been thinking
+    /// for `thinking` since the clock was read
+    pub fn out_of_time(&self, thinking: Duration) -> bool {
+        self.time_left(self.player, thinking) == Some(Duration::from_secs(0))
     }
 
     #[allow(dead_code)]
     fn validate_submove(&self, s: &Submove) -> Result<bool, String> {
-        self.board.validate_submove(&s, self.player)
+        self.board
+            .validate_submove_with(&s.norm(self.player), self.player, self.variant)
+    }
+
+    pub fn validate_move(&self, m: &Move) -> Result<bool, String> {
+        self.board.validate_move_with(m, self.player, self.variant)
+    }
+
+    /// Lists every legal turn for the player to move
+    pub fn legal_moves(&self) -> Moves {
+        self.board.legal_moves_with(self.player, self.variant)
+    }
+
+    /// The pieces which may be moved, in the order of their squares. Part way
+    /// through a jump sequence, only the jumping piece may move.
+    pub fn movable_pieces(&self) -> Vec<Position> {
+        if let Some(m) = &self.turn {
+            return vec![m.to()];
+        }
+        let mut pieces: Vec<Position> = Vec::new();
+        for m in self.legal_moves() {
+            if !pieces.contains(&m.from) {
+                pieces.push(m.from);
+            }
+        }
+        pieces.sort_by_key(|p| p.square());
+        pieces
+    }
+
+    /// Reads a turn for the player to move written in standard notation, e.g.
+    /// `11-15` or `15x22`.
+    pub fn parse_move(&self, s: &str) -> Result<Move, String> {
+        let squares = moves::parse_squares(s)?;
+        self.board
+            .build_move(squares[0], &squares[1..], self.player, self.variant)
     }
 
     /// Checks the win condition for each player. This is to be run at the end of each turn.
     ///
-    /// TODO: Add the case in which a player can make no possible moves.
+    /// A player loses once all of their pieces have been captured, or if it
+    /// is their turn and they have no legal move.
     pub fn win_condition(&self) -> Option<Player> {
         if self.board.count_pieces(Player::Black) == 0 {
             Some(Player::White)
         } else if self.board.count_pieces(Player::White) == 0 {
             Some(Player::Black)
+        } else if self.turn.is_none() && self.legal_moves().is_empty() {
+            Some(self.player.switch())
         } else {
             None
         }
     }
 
-    /// Calculate all possible moves from position `p` for player `Game::Player`.
-    pub fn gen_submoves(&mut self, p: Position) {
-        // Calculate single space moves
-        let directional_moves_from = |from: Position| match self.board.at(&p) {
-            Some(Piece::King(_)) => {
-                let neighbors = vec![(-1, -1), (-1, 1), (-1, 1), (1, 1)];
-                neighbors
-                    .iter()
-                    .map(|(x, y)| (from.0 as i32 - x, from.1 as i32 - y))
-                    .collect()
-            }
-            Some(Piece::Normal(_)) => {
-                let neighbors = vec![(1, 1), (1, -1)];
-                neighbors
-                    .iter()
-                    .map(|(x, y)| (from.0 as i32 - x, from.1 as i32 - y))
-                    .collect()
-            }
-            _ => vec![],
-        };
-
-        // Filter out-of-bounds coordinates
-        let filter_oob = |v: Vec<(i32, i32)>| {
-            v.iter()
-                .filter(|(x, y)| {
-                    *x >= 0 && *x < BOARD_WIDTH as i32 && *y >= 0 && *y < BOARD_WIDTH as i32
-                })
-                .map(|(x, y)| (*x, *y))
-                .collect()
-        };
-
-        // Generate initial possible moves
-        let possible: Vec<(i32, i32)> = filter_oob(directional_moves_from(p));
-
-        // Generate normal moves
-        let mut normal_only: Vec<Position> = possible
-            .iter()
-            .filter(|&x| self.board.at(&Position::from(*x)).is_none())
-            .map(|&x| Position::from(x))
-            .collect();
-
-        // Add normal moves to hilighted
-        self.hilighted.append(&mut normal_only);
-
-        // Get attacked pieces
-        let attacked: Vec<(i32, i32)> = possible
-            .iter()
-            .filter(|&x| self.board.at(&Position::from(*x)).is_some())
-            .filter(|&x| {
-                self.board.at(&Position::from(*x)).unwrap().player() == self.player.switch()
-            })
-            .map(|&x| x)
-            .collect();
-
-        for &v in attacked.iter() {
-            let diff = (v.0 as i32 - p.0 as i32, v.1 as i32 - p.1 as i32);
-            let jump = Position::from((v.0 + diff.0, v.1 + diff.1));
-            if !filter_oob(vec![(jump.0 as i32, jump.1 as i32)]).is_empty()
-                && self.board.at(&jump).is_none()
-            {
-                self.hilighted.push(Position::from(jump));
+    /// Lists the submoves which may currently be played by the piece at `p`.
+    /// Part way through a jump sequence, only the jumping piece may move.
+    fn submoves_from(&self, p: Position) -> Vec<Submove> {
+        match &self.turn {
+            Some(m) if m.to() == p => self.board.captures_from(p, self.player, self.variant),
+            Some(_) => vec![],
+            None => {
+                let mut submoves: Vec<Submove> = Vec::new();
+                for m in self.legal_moves().iter().filter(|m| m.from == p) {
+                    let s = m.submoves().remove(0);
+                    if !submoves.contains(&s) {
+                        submoves.push(s);
+                    }
+                }
+                submoves
             }
         }
     }
 
+    /// Calculate all possible moves from position `p` for player `Game::Player`.
+    pub fn gen_submoves(&mut self, p: Position) {
+        let mut destinations = self.submoves_from(p).into_iter().map(|s| s.to).collect();
+        self.hilighted.append(&mut destinations);
+    }
+
     pub fn select(&mut self, p: Position) {
+        // A jumping piece must finish its turn
+        if self.turn.is_some() {
+            return;
+        }
         self.hilighted.clear();
         match self.board.at(&p) {
             Some(v) => {
@@ -124,45 +184,100 @@ impl Game {
         }
     }
 
+    /// Ends the current turn and hands the board to the other player
+    fn end_turn(&mut self, m: Move) {
+        self.history.push(m);
+        self.player = self.player.switch();
+        self.turn = None;
+        self.selected = None;
+        self.hilighted.clear();
+    }
+
     /// # Steps
     /// 1. Validate submove
     /// 2. Mutate board
-    /// 3. Change player
+    /// 3. Change player once the turn is complete
     pub fn do_submove(&mut self, s: &Submove) -> Result<bool, String> {
-        // XXX: broken because of mutating board rotation
-        //self.validate_submove(s)?;
-        let piece = self.board.at(&s.from).unwrap();
+        if let Some(m) = &self.turn {
+            if m.to() != s.from {
+                return Err(format!("The piece at {} must finish its jump.", m.to()));
+            }
+        }
+        self.validate_submove(s)?;
+        if !self.submoves_from(s.from).contains(s) {
+            let must_capture =
+                self.turn.is_none() && self.board.can_capture(self.player, self.variant);
+            return Err(match must_capture {
+                true => String::from("A capture is available and must be taken."),
+                _ => format!("Submove {} -> {} is not legal.", s.from, s.to),
+            });
+        }
 
-        self.board.remove(&s.from);
+        let (captured, promoted) = self.board.step(s, self.player);
+        let mut m = self.turn.take().unwrap_or_else(|| Move::new(s.from));
+        m.path.push(s.to);
+        m.captured.extend(captured);
+        m.promoted = promoted;
 
-        let diff = (
-            s.to.0 as i32 - s.from.0 as i32,
-            s.to.1 as i32 - s.from.1 as i32,
-        );
-        if diff.0.abs() > 1 || diff.1.abs() > 1 {
-            // normalize diff
-            let diff = (diff.0 / diff.0.abs(), diff.1 / diff.1.abs());
-            // add diff to source
-            let remove_pos = Position::from((s.from.0 as i32 + diff.0, s.from.1 as i32 + diff.1));
-            self.board.remove(&remove_pos);
+        if m.is_capture()
+            && !promoted
+            && !self
+                .board
+                .captures_from(s.to, self.player, self.variant)
+                .is_empty()
+        {
+            self.turn = Some(m);
+            self.selected = Some(s.to);
+            self.hilighted.clear();
+            self.gen_submoves(s.to);
         } else {
-            self.nmoves -= 1;
-        }
-        //check promotion. am not checking for piece type
-        if((s.to.0 as i32) == 0){
-            self.board.place(Piece::King(self.player), &s.to);
+            self.end_turn(m);
         }
-        else{
-            self.board.place(piece, &s.to);
+        Ok(true)
+    }
+
+    /// Plays a complete turn for the player to move
+    pub fn do_move(&mut self, m: &Move) -> Result<bool, String> {
+        if self.turn.is_some() {
+            return Err(String::from("A turn is already in progress."));
         }
+        self.validate_move(m)?;
+        self.board.apply(m);
+        self.end_turn(m.clone());
+        Ok(true)
+    }
 
-        if self.nmoves == 0 {
-            self.player = self.player.switch();
-            self.board.flip();
-            self.nmoves = 1;
+    /// Takes back the turn in progress, or the last completed turn if there
+    /// is none, returning the move which was taken back.
+    pub fn undo(&mut self) -> Option<Move> {
+        let m = match self.turn.take() {
+            Some(m) => m,
+            None => {
+                let m = self.history.pop()?;
+                self.player = self.player.switch();
+                m
+            }
+        };
+        self.board.undo(&m);
+        self.selected = None;
+        self.hilighted.clear();
+        Some(m)
+    }
+
+    /// The game as it stood once the first `n` turns of its history had been
+    /// played, found by taking back the rest. The game itself is untouched.
+    pub fn position_after(&self, n: usize) -> Game {
+        let mut game = self.clone();
+        while game.turn.is_some() || game.history.len() > n {
+            game.undo();
         }
+        game
+    }
+}
 
-        Ok(true)
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
     }
 }
 
@@ -175,28 +290,118 @@ mod tests {
         let mut g = Game::new();
         let submove = Submove::new((5, 0), (4, 1));
         let b = Board::from(
-            "-b-b-b-b\
-             b-b-b-b-\
-             -b-b-b--\
-             ------b-\
-             --------\
+            "-w-w-w-w\
              w-w-w-w-\
              -w-w-w-w\
-             w-w-w-w-",
+             --------\
+             -b------\
+             --b-b-b-\
+             -b-b-b-b\
+             b-b-b-b-",
         );
         g.do_submove(&submove).unwrap();
+        let mut m = Move::new((5, 0));
+        m.path.push(Position::new(4, 1));
         assert_eq!(
             g,
             Game {
                 board: b,
                 player: Player::White,
+                variant: Variant::American,
                 selected: None,
                 hilighted: Vec::new(),
-                nmoves: 1,
+                turn: None,
+                history: vec![m],
+                black_name: String::from("Black"),
+                white_name: String::from("White"),
+                clock: None,
             }
         );
     }
 
+    #[test]
+    fn do_submove_jump() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             --w-----\
+             --------\
+             --w-----\
+             -b------\
+             --------",
+        );
+        g.do_submove(&Submove::new((6, 1), (4, 3))).unwrap();
+        assert_eq!(g.player, Player::Black);
+        assert_eq!(g.selected, Some(Position::new(4, 3)));
+        assert_eq!(g.hilighted, vec![Position::new(2, 1)]);
+        assert_eq!(
+            g.do_submove(&Submove::new((4, 3), (3, 4))),
+            Err(String::from("Submove (4, 3) -> (3, 4) is not legal."))
+        );
+        g.do_submove(&Submove::new((4, 3), (2, 1))).unwrap();
+        assert_eq!(g.player, Player::White);
+        assert_eq!(g.history[0].to_string(), "8x15x24");
+        assert_eq!(g.board.count_pieces(Player::White), 0);
+    }
+
+    #[test]
+    fn do_submove_mandatory_capture() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             --------\
+             --w-----\
+             -b-b----\
+             --------",
+        );
+        assert_eq!(
+            g.do_submove(&Submove::new((6, 3), (5, 4))),
+            Err(String::from("A capture is available and must be taken."))
+        );
+    }
+
+    #[test]
+    fn do_move_white() {
+        let mut g = Game::new();
+        g.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+        let m = g.board.build_move(
+            Position::new(2, 1),
+            &[Position::new(3, 0)],
+            Player::White,
+            Variant::American,
+        );
+        assert_eq!(g.do_move(&m.unwrap()), Ok(true));
+        assert_eq!(g.player, Player::Black);
+        assert_eq!(g.history.len(), 2);
+    }
+
+    #[test]
+    fn parse_move() {
+        let g = Game::new();
+        assert_eq!(g.parse_move("11-15").unwrap().to_string(), "11-15");
+        assert_eq!(
+            g.parse_move("11-14"),
+            Err(String::from(
+                "Pieces must move diagonally: (5, 2) -> (4, 5)"
+            ))
+        );
+    }
+
+    #[test]
+    fn undo() {
+        let mut g = Game::new();
+        g.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+        assert_eq!(g.undo().map(|m| m.to_string()), Some(String::from("12-16")));
+        assert_eq!(g.board, Board::new());
+        assert_eq!(g.player, Player::Black);
+        assert_eq!(g.undo(), None);
+    }
+
     #[test]
     fn validate_submove_exists() {
         let g = Game::new();
@@ -218,7 +423,18 @@ mod tests {
     #[test]
     fn validate_submove_ok() {
         let g = Game::new();
-        assert_eq!(g.validate_submove(&Submove::new((7, 0), (0, 0))), Ok(true));
+        assert_eq!(g.validate_submove(&Submove::new((5, 0), (4, 1))), Ok(true));
+    }
+
+    #[test]
+    fn validate_submove_diagonal() {
+        let g = Game::new();
+        assert_eq!(
+            g.validate_submove(&Submove::new((7, 0), (0, 0))),
+            Err(String::from(
+                "Pieces must move diagonally: (7, 0) -> (0, 0)"
+            ))
+        );
     }
 
     #[test]
@@ -232,20 +448,120 @@ mod tests {
              --------\
              b-b-b-b-\
              -b-b-b-b\
-             b-b-b-b-");
+             b-b-b-b-",
+        );
         assert_eq!(g.win_condition(), Some(Player::Black));
     }
 
+    #[test]
+    fn win_condition_blocked() {
+        let mut g = Game::new();
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             --------\
+             --w-----\
+             -w------\
+             b-------\
+             --------",
+        );
+        assert_eq!(g.win_condition(), Some(Player::White));
+    }
+
     #[test]
     fn win_condition_none() {
         let g = Game::new();
         assert_eq!(g.win_condition(), None);
     }
 
+    #[test]
+    fn out_of_time() {
+        let mut g = Game::new();
+        assert!(!g.out_of_time(Duration::from_secs(3600)));
+        g.clock = Some(Clock::new("1".parse().unwrap()));
+        assert!(!g.out_of_time(Duration::from_secs(59)));
+        assert!(g.out_of_time(Duration::from_secs(60)));
+        assert_eq!(
+            g.time_left(Player::White, Duration::from_secs(60)),
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn movable_pieces() {
+        let mut g = Game::new();
+        let squares: Vec<_> = g.movable_pieces().iter().map(|p| p.square()).collect();
+        assert_eq!(squares, vec![Some(9), Some(10), Some(11), Some(12)]);
+
+        g.board = Board::from(
+            "--------\
+             --------\
+             --------\
+             --w-----\
+             --------\
+             --w-----\
+             -b------\
+             --------",
+        );
+        g.do_submove(&Submove::new((6, 1), (4, 3))).unwrap();
+        assert_eq!(g.movable_pieces(), vec![Position::new(4, 3)]);
+    }
+
+    #[test]
+    fn position_after() {
+        let mut g = Game::new();
+        g.do_move(&g.parse_move("11-15").unwrap()).unwrap();
+        let first = g.clone();
+        g.do_move(&g.parse_move("22-18").unwrap()).unwrap();
+        assert_eq!(g.position_after(1), first);
+        assert_eq!(g.position_after(0), Game::new());
+        assert_eq!(g.position_after(2), g);
+        assert_eq!(g.history.len(), 2);
+    }
+
+    #[test]
+    fn rematch() {
+        let mut g = Game::new();
+        g.black_name = String::from("Eric");
+        g.white_name = String::from("Ernie");
+        g.clock = Some(Clock::new("5".parse().unwrap()));
+        g.do_move(&g.parse_move("11-15").unwrap()).unwrap();
+
+        let again = g.rematch(false);
+        assert_eq!(again.board, Board::new());
+        assert_eq!(again.player, Player::Black);
+        assert!(again.history.is_empty());
+        assert_eq!(again.name(Player::Black), "Eric");
+        assert_eq!(again.clock, Some(Clock::new("5".parse().unwrap())));
+
+        let swapped = g.rematch(true);
+        assert_eq!(swapped.name(Player::Black), "Ernie");
+        assert_eq!(swapped.name(Player::White), "Eric");
+    }
+
     #[test]
     fn gen_submoves_move() {
         let mut g = Game::new();
         g.gen_submoves(Position::new(5, 0));
         assert_eq!(g.hilighted, vec![Position::new(4, 1)]);
     }
+
+    #[test]
+    fn serialize_json() {
+        let mut g = Game::new();
+        g.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+        let json = serde_json::to_string(&g).unwrap();
+        assert_eq!(serde_json::from_str::<Game>(&json).unwrap(), g);
+    }
+
+    #[test]
+    fn serialize_bincode() {
+        let mut g = Game::new();
+        g.select(Position::new(5, 0));
+        let bytes = bincode::serialize(&g).unwrap();
+        let decoded: Game = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.board, g.board);
+        assert_eq!(decoded.selected, None);
+    }
 }
@@ -0,0 +1,26 @@
+//! The rules engine behind Rusted Checkers.
+//!
+//! The board, moves, and game state may be used independently of the ncurses
+//! front-end, e.g.
+//!
+//! ```
+//! use rustedcheckers::game::Game;
+//! use rustedcheckers::moves::Submove;
+//!
+//! let mut game = Game::new();
+//! game.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+//! assert_eq!(game.history[0].to_string(), "12-16");
+//! ```
+
+#[macro_use]
+extern crate serde_derive;
+
+pub mod ai;
+pub mod board;
+pub mod clock;
+pub mod game;
+pub mod moves;
+pub mod networking;
+pub mod piece;
+pub mod player;
+pub mod save;
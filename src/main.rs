@@ -1,386 +1,167 @@
-pub mod board;
-pub mod game;
-pub mod moves;
-pub mod networking;
-pub mod piece;
-pub mod player;
+//! The ncurses front-end to Rusted Checkers
 
 #[macro_use]
 extern crate serde_derive;
 extern crate docopt;
-extern crate bincode;
 
 use docopt::Docopt;
-use networking::{client, server};
+use rustedcheckers::networking::client;
+use rustedcheckers::networking::server::{self, Config};
+use rustedcheckers::networking::Settings;
+use rustedcheckers::save;
 
-const USAGE: &'static str = "
+const USAGE: &str = "
 Rusted Checkers
 
+Run without arguments to choose a game from the menu. Games played
+here are saved after every turn to autosave.json under
+$XDG_DATA_HOME/rustedcheckers (~/.local/share/rustedcheckers if unset);
+pick Resume last game from the menu, or pass it to --load, to go on.
+
 Usage:
-  rustedcheckers
-  rustedcheckers server
-  rustedcheckers client
+  rustedcheckers [--load <file>] [--time <control>]
+  rustedcheckers connect [--port <port>]
+  rustedcheckers host [--bind <addr>] [--port <port>] [--color <color>] [--time <control>]
+  rustedcheckers join <addr> [--port <port>] [--color <color>]
+  rustedcheckers server [--bind <addr>] [--port <port>] [--time <control>]
+  rustedcheckers client [--host <host>] [--port <port>]
   rustedcheckers (-h | --help)
   rustedcheckers --version
 
 Options:
-  -h --help     Show this screen.
-  -v --version  Show version.
+  -h --help          Show this screen.
+  -v --version       Show version.
+  --load <file>      Resume a saved game.
+  --bind <addr>      Address to listen on, or $RUSTEDCHECKERS_BIND.
+                     Every interface if neither is given.
+  --host <host>      Server to connect to, or $RUSTEDCHECKERS_HOST.
+                     localhost if neither is given.
+  --port <port>      Port to play on, or $RUSTEDCHECKERS_PORT.
+                     3333 if neither is given.
+  --color <color>    Ask to play black or white.
+  --time <control>   Play with a clock: 5 for five minutes each, 5+3 to
+                     add three seconds a turn, 5d3 to give back up to
+                     three seconds a turn, or 40/90 for ninety minutes
+                     every forty turns.
 ";
 
-
 extern crate ncurses;
 
-use crate::board::{Board, Position, BOARD_WIDTH};
-use crate::game::Game;
-use crate::moves::Submove;
-use crate::piece::Piece;
-use crate::player::Player;
+mod ui;
+
+use crate::ui::menu::menu;
+use crate::ui::play::{new_game, play};
+use crate::ui::prefs::{self, Prefs};
+use crate::ui::theme::start_curses;
+use crate::ui::Mode;
+
+use rustedcheckers::clock::{Clock, TimeControl};
+use rustedcheckers::game::Game;
+use rustedcheckers::player::Player;
 
-use libc::{c_int, c_short};
-use ncurses::constants::{COLOR_BLACK, COLOR_WHITE};
 use ncurses::*;
 
-use std::char;
+use std::path::PathBuf;
+use std::process;
 use std::str;
 
 #[derive(Debug, Deserialize)]
 struct Args {
+    cmd_connect: bool,
+    cmd_host: bool,
+    cmd_join: bool,
     cmd_server: bool,
     cmd_client: bool,
+    arg_addr: Option<String>,
+    flag_load: Option<String>,
+    flag_bind: Option<String>,
+    flag_host: Option<String>,
+    flag_port: Option<String>,
+    flag_color: Option<String>,
+    flag_time: Option<String>,
 }
 
-// Individual Colors
-static COLOR_BACKGROUND: i16 = 230;
-static COLOR_FOREGROUND: i16 = COLOR_BLACK;
-static COLOR_RED: i16 = 9;
-static COLOR_BLUE: i16 = 12;
-static COLOR_HI: i16 = 3;
-static COLOR_GREEN: i16 = 3;
-
-// Color Pairs
-static COLOR_PAIR_DEFAULT: i16 = 1;
-static COLOR_PAIR_RED_ON_BLACK: i16 = 2;
-static COLOR_PAIR_BLUE_ON_BLACK: i16 = 3;
-static COLOR_PAIR_RED_ON_WHITE: i16 = 4;
-static COLOR_PAIR_BLUE_ON_WHITE: i16 = 5;
-static COLOR_PAIR_WHITE: i16 = 6;
-static COLOR_PAIR_BLACK: i16 = 7;
-static COLOR_PAIR_RED_ON_BKGD: i16 = 8;
-static COLOR_PAIR_BLUE_ON_BKGD: i16 = 9;
-static COLOR_PAIR_RED_HI: i16 = 10;
-static COLOR_PAIR_BLUE_HI: i16 = 11;
-static COLOR_PAIR_EMPTY_HI: i16 = 12;
-
-// Window Positioning
-static TITLE_POS: (i32, i32) = (0, 0);
-static TITLE_H: i32 = 3;
-static BOARD_POS: (i32, i32) = (TITLE_POS.0 + TITLE_H, 0);
-static BOARD_H: i32 = BOARD_WIDTH as i32 + 2;
-static BOARD_W: i32 = BOARD_WIDTH as i32 * 2 + 2;
-static CAPTURE_POS: (i32, i32) = (BOARD_POS.0 + BOARD_H, 0);
-static CAPTURE_H: i32 = 4;
-static CAPTURE_W: i32 = 2 + 2 + 1 + 12 * 2;
-
-/// Creates, refreshes, and returns a bordered window
-fn create_win(height: i32, width: i32, start_y: i32, start_x: i32) -> WINDOW {
-    let win = newwin(height, width, start_y, start_x);
-    wbkgd(win, COLOR_PAIR(COLOR_PAIR_DEFAULT));
-    box_(win, 0, 0);
-    wrefresh(win);
-    win
-}
-
-/// Destroys and refreshes a specified window
-fn destroy_win(win: WINDOW) {
-    let ch = ' ' as chtype;
-    wborder(win, ch, ch, ch, ch, ch, ch, ch, ch);
-    wrefresh(win);
-    delwin(win);
-}
-
-fn draw_captured(win: WINDOW, board: &Board) {
-    // Get number of captured pieces
-    let w_cap = 12 - board.count_pieces(Player::White);
-    let b_cap = 12 - board.count_pieces(Player::Black);
-
-    // Title
-    mvwaddstr(win, 0, 1, "┤Captured├");
-
-    // Draw captured red pieces
-    wcolor_set(win, COLOR_PAIR_RED_ON_BKGD);
-    mvwaddstr(win, 1, 1, &w_cap.to_string());
-    if w_cap > 0 {
-        for x in 2..(w_cap + 2) {
-            mvwaddstr(win, 1, x as i32 * 2, "⬤ ");
-        }
+fn parse_color(s: &str) -> Result<Player, String> {
+    match s.to_lowercase().as_str() {
+        "black" => Ok(Player::Black),
+        "white" => Ok(Player::White),
+        _ => Err(format!("'{}' is not a colour; choose black or white.", s)),
     }
-
-    // Draw captured blue pieces
-    wcolor_set(win, COLOR_PAIR_BLUE_ON_BKGD);
-    mvwaddstr(win, 2, 1, &b_cap.to_string());
-    if b_cap > 0 {
-        for y in 2..(b_cap + 2) {
-            mvwaddstr(win, 2, y as i32 * 2, "⬤ ");
-        }
-    }
-
-    wrefresh(win);
 }
 
-fn draw_board(win: WINDOW, game: &mut Game) {
-    // Title
-    mvwaddstr(win, 0, 1, "┤Board├");
-
-    // Board
-    for (i, x) in game.board.board.iter().enumerate() {
-        for (j, y) in x.iter().enumerate() {
-            match y {
-                Some(Piece::Normal(Player::White)) => {
-                    if (i + j) % 2 == 1 {
-                        wcolor_set(win, COLOR_PAIR_RED_ON_BLACK);
-                    } else {
-                        wcolor_set(win, COLOR_PAIR_RED_ON_WHITE);
-                    }
-                }
-                Some(Piece::Normal(Player::Black)) => {
-                    if (i + j) % 2 == 1 {
-                        wcolor_set(win, COLOR_PAIR_BLUE_ON_BLACK);
-                    } else {
-                        wcolor_set(win, COLOR_PAIR_BLUE_ON_WHITE);
-                    }
-                }
-                Some(Piece::King(Player::White)) => {
-                    if (i + j) % 2 == 1 {
-                        wcolor_set(win, COLOR_PAIR_RED_ON_BLACK);
-                    } else {
-                        wcolor_set(win, COLOR_PAIR_RED_ON_WHITE);
-                    }
-                }
-                Some(Piece::King(Player::Black)) => {
-                    if (i + j) % 2 == 1 {
-                        wcolor_set(win, COLOR_PAIR_BLUE_ON_BLACK);
-                    } else {
-                        wcolor_set(win, COLOR_PAIR_BLUE_ON_WHITE);
-                    }
-                }
-                _ => {
-                    if (i + j) % 2 == 1 {
-                        wcolor_set(win, COLOR_PAIR_BLACK);
-                    } else {
-                        wcolor_set(win, COLOR_PAIR_WHITE);
-                    }
-                }
-            }
-
-            match &game.selected {
-                Some(p) => {
-                    if Position::new(i, j) == *p {
-                        match game.board.at(p) {
-                            Some(Piece::Normal(Player::Black)) => {
-                                wcolor_set(win, COLOR_PAIR_BLUE_HI)
-                            }
-                            Some(Piece::Normal(Player::White)) => {
-                                wcolor_set(win, COLOR_PAIR_RED_HI)
-                            }
-                            Some(Piece::King(Player::Black)) => {
-                                wcolor_set(win, COLOR_PAIR_BLUE_HI)
-                            }
-                            Some(Piece::King(Player::White)) => {
-                                wcolor_set(win, COLOR_PAIR_RED_HI)
-                            }
-                            _ => 0,
-                        };
-                    }
-                }
-                _ => (),
-            }
-
-            if game.hilighted.contains(&Position::new(i, j)) {
-                wcolor_set(win, COLOR_PAIR_EMPTY_HI);
-                mvwaddstr(win, i as i32 + 1, j as i32 * 2 + 1, "  ");
-            } else {
-                   match y{
-                            Some(Piece::King(_)) => {
-                                mvwaddstr(win, i as i32 + 1, j as i32 * 2 + 1, "❤");
-                            }
-                            _ => {
-                                mvwaddstr(win, i as i32 + 1, j as i32 * 2 + 1, "⬤ ");
-                            },
-                        };
-            }
-            wattroff(win, A_DIM());
-        }
-    }
-
-    // Reset & Refresh
-    wcolor_set(win, COLOR_PAIR_DEFAULT);
-    wrefresh(win);
-}
-
-const fn new_mevent() -> MEVENT {
-    MEVENT {
-        id: 0 as c_short,
-        x: 0 as c_int,
-        y: 0 as c_int,
-        z: 0 as c_int,
-        bstate: 0 as u64,
-    }
-}
-
-fn clearline(line: i32) {
-    mv(line, 0);
-    clrtoeol();
+/// Reports an error which stops the game from starting and exits
+fn fail(e: &str) -> ! {
+    eprintln!("{}", e);
+    process::exit(1);
 }
 
 fn main() {
-    // Unicode
-    let locale_conf = LcCategory::all;
-    setlocale(locale_conf, "en_US.UTF-8");
-
-    // ncurses init
-    initscr();
-    cbreak();
-    noecho();
-    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
-    keypad(stdscr(), true);
-
-    // Get screen size
-    let mut max_x = 0;
-    let mut max_y = 0;
-    getmaxyx(stdscr(), &mut max_y, &mut max_x);
-
-    // Coloring
-    start_color();
-    init_pair(COLOR_PAIR_DEFAULT, COLOR_FOREGROUND, COLOR_BACKGROUND);
-    init_pair(COLOR_PAIR_RED_ON_BLACK, COLOR_RED, COLOR_BLACK);
-    init_pair(COLOR_PAIR_BLUE_ON_BLACK, COLOR_BLUE, COLOR_BLACK);
-    init_pair(COLOR_PAIR_RED_ON_WHITE, COLOR_RED, COLOR_WHITE);
-    init_pair(COLOR_PAIR_BLUE_ON_WHITE, COLOR_BLUE, COLOR_WHITE);
-    init_pair(COLOR_PAIR_WHITE, COLOR_WHITE, COLOR_WHITE);
-    init_pair(COLOR_PAIR_BLACK, COLOR_BLACK, COLOR_BLACK);
-    init_pair(COLOR_PAIR_BLUE_ON_BKGD, COLOR_BLUE, COLOR_BACKGROUND);
-    init_pair(COLOR_PAIR_RED_ON_BKGD, COLOR_RED, COLOR_BACKGROUND);
-    init_pair(COLOR_PAIR_RED_HI, COLOR_RED, COLOR_HI);
-    init_pair(COLOR_PAIR_BLUE_HI, COLOR_BLUE, COLOR_HI);
-    init_pair(COLOR_PAIR_EMPTY_HI, COLOR_WHITE, COLOR_GREEN);
-    bkgd(' ' as chtype | COLOR_PAIR(COLOR_PAIR_DEFAULT) as chtype);
-
-    // Mouse
-    mousemask(ALL_MOUSE_EVENTS as mmask_t, None);
-
-    refresh();
-
-    // Title
-    mvaddstr(
-        0,
-        0,
-        "╔═════════════════╗",
-    );
-    mvaddstr(1, 0, "║ Rusted Checkers ║");
-    mvaddstr(
-        2,
-        0,
-        "╚═════════════════╝",
-    );
-
-    //Create test board
-    let mut game = Game::new();
-    //game.board.mutate(&Submove::new((5, 0), (2, 1)));
-    //game.board.mutate(&Submove::new((2, 1), (1, 2)));
-    //game.board.mutate(&Submove::new((2, 3), (5, 2)));
-    game.board.mutate(&Submove::new((2, 1), (4, 1)));
-    //game.board.mutate(&Submove::new((1, 4), (4, 1)));
-
-    // Create wide window for the board
-    let board_win = create_win(BOARD_H, BOARD_W, BOARD_POS.0, BOARD_POS.1);
-    draw_board(board_win, &mut game);
-
-    // Create captured window
-    let captured_win = create_win(CAPTURE_H, CAPTURE_W, CAPTURE_POS.0, CAPTURE_POS.1);
-    draw_captured(captured_win, &game.board);
-
-    refresh();
-
-    // Input
-    'main: loop {
-        let ch = wget_wch(stdscr());
-        match ch {
-            Some(WchResult::KeyCode(KEY_MOUSE)) => {
-                // Get stdscr coords
-                let mut mevent = new_mevent();
-                let err_code = getmouse(&mut mevent);
-                if err_code == 0 {
-                    mvaddstr(LINES() - 1, 0, format!("{:?}", mevent).as_str());
-                } else {
-                    panic!("Couldn't get mouse event.");
-                }
-                // Normalize to board_win
-                let xs: &mut [i32] = &mut [mevent.x];
-                let ys: &mut [i32] = &mut [mevent.y];
-                let in_win = wmouse_trafo(board_win, ys, xs, false);
-                if in_win {
-                    let selected_pos =
-                        Position::new((ys[0] - 1) as usize, ((xs[0] - 1) / 2) as usize);
-                    if game.hilighted.contains(&selected_pos) {
-                        let res = game.do_submove(&Submove {
-                            from: game.selected.unwrap(),
-                            to: selected_pos,
-                        });
-                        if res.is_err() {
-                            mvaddstr(LINES() - 5, 0, res.err().unwrap().as_str());
-                        } else {
-                            game.hilighted.clear();
-                            draw_board(board_win, &mut game);
-                            draw_captured(captured_win, &game.board);
-                        }
-                    } else {
-                        game.select(selected_pos);
-                        // Select clicked piece
-                        game.select(Position::new(
-                            (ys[0] - 1) as usize,
-                            ((xs[0] - 1) / 2) as usize,
-                        ));
-                        //game.selected = Some(Position::new(0, 1));
-                        mvaddstr(
-                            LINES() - 4,
-                            0,
-                            format!("Selected: {:?}", game.selected).as_str(),
-                        );
-                        draw_board(board_win, &mut game);
-                    }
-                }
-            }
-            Some(WchResult::KeyCode(_)) => {
-                let line = LINES() - 3;
-                clearline(line);
-                mvaddstr(line, 0, "Keycode pressed.");
-            }
-            Some(WchResult::Char(c)) => {
-                /* Enable attributes and output message. */
-                addstr("\nKey pressed: ");
-                attron(A_BOLD() | A_BLINK());
-                addstr(format!("{}\n", char::from_u32(c as u32).expect("Invalid char")).as_ref());
-                attroff(A_BOLD() | A_BLINK());
-                match char::from_u32(c as u32).unwrap() {
-                    'q' => break 'main,
-                    _ => (),
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| {
+            d.version(Some(env!("CARGO_PKG_VERSION").to_string()))
+                .deserialize()
+        })
+        .unwrap_or_else(|e| e.exit());
+
+    let settings = Settings::new(args.flag_bind, args.flag_host, args.flag_port)
+        .unwrap_or_else(|e| fail(&e));
+    let time = args
+        .flag_time
+        .map(|t| t.parse::<TimeControl>().unwrap_or_else(|e| fail(&e)));
+    if args.cmd_server {
+        let config = Config {
+            time,
+            ..Config::default()
+        };
+        return server::main(&settings, config).unwrap_or_else(|e| fail(&e));
+    }
+    if args.cmd_client {
+        return client::main(&settings).unwrap_or_else(|e| fail(&e));
+    }
+    let prefs = Prefs::load(prefs::default_path()).unwrap_or_else(|e| fail(&e));
+    let color = match args.flag_color {
+        Some(c) => Some(parse_color(&c).unwrap_or_else(|e| fail(&e))),
+        None => prefs.color,
+    };
+    let mode = if args.cmd_connect {
+        Mode::Connect(settings)
+    } else if args.cmd_host {
+        Mode::Host(settings, color, time.or(prefs.time))
+    } else if args.cmd_join {
+        Mode::Join(args.arg_addr.unwrap_or_default(), settings, color)
+    } else if args.flag_load.is_none() && time.is_none() {
+        start_curses(&prefs);
+        menu(&settings, prefs);
+        endwin();
+        return;
+    } else {
+        Mode::Local
+    };
+
+    let (game, save_path, mode) = match (mode, args.flag_load) {
+        (Mode::Local, Some(path)) => match save::load(&path) {
+            Ok((mut game, computer)) => {
+                if let Some(time) = time {
+                    game.clock = Some(Clock::new(time));
                 }
+                (game, PathBuf::from(path), Mode::local(computer))
             }
-            _ => (),
+            Err(e) => fail(&e),
+        },
+        (Mode::Local, None) => (
+            new_game(&prefs, time, None),
+            save::default_path(),
+            Mode::Local,
+        ),
+        (mode, _) => {
+            start_curses(&prefs);
+            let played = play(Game::new(), &save::default_path(), mode, &prefs);
+            endwin();
+            return played.unwrap_or_else(|e| fail(&e));
         }
-    }
-
-    destroy_win(board_win);
+    };
+    start_curses(&prefs);
+    let played = play(game, &save_path, mode, &prefs);
     endwin();
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn _main() {
-        main();
-    }
+    played.unwrap_or_else(|e| fail(&e));
 }
@@ -1,9 +1,14 @@
-use crate::board::{Position};
+use crate::board::Position;
+use crate::piece::Piece;
+use crate::player::Player;
 
 use std::convert::Into;
-use std::ops::{Add, Sub};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+/// A single step of a turn: either a move to an adjacent square or a jump
+///
+/// Serialized as a map of its two positions, e.g. `{"from":[5,0],"to":[4,1]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Submove {
     pub from: Position,
     pub to: Position,
@@ -16,49 +21,194 @@ impl Submove {
             to: to.into(),
         }
     }
-}
 
-impl Add for Submove {
-    type Output = Submove;
+    /// The signed (rows, columns) distance travelled by the submove
+    pub fn delta(&self) -> (i32, i32) {
+        (
+            self.to.0 as i32 - self.from.0 as i32,
+            self.to.1 as i32 - self.from.1 as i32,
+        )
+    }
 
-    fn add(self, other: Submove) -> Submove {
+    /// Returns the submove as seen from the [`Player`](../player/enum.Player.html)'s
+    /// perspective. See [`Position::norm()`](../board/struct.Position.html#method.norm).
+    pub fn norm(&self, p: Player) -> Submove {
         Submove {
-            from: self.from + other.from,
-            to: self.to + other.to,
+            from: self.from.norm(p),
+            to: self.to.norm(p),
         }
     }
 }
 
-impl Sub for Submove {
-    type Output = Submove;
+/// A full turn. The piece at `from` lands on each square of `path` in order,
+/// capturing the pieces in `captured` along the way.
+///
+/// Positions are given as they appear on the board, with `Player::Black` on
+/// the bottom.
+///
+/// Serialized as a map of its fields, with each captured piece given as its
+/// position followed by the piece, e.g.
+/// `{"from":[4,3],"path":[[2,5]],"captured":[[[3,4],{"Normal":"White"}]],"promoted":false}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    pub from: Position,
+    pub path: Vec<Position>,
+    pub captured: Vec<(Position, Piece)>,
+    pub promoted: bool,
+}
 
-    fn sub(self, other: Submove) -> Submove {
-        Submove {
-            from: self.from - other.from,
-            to: self.to - other.to,
+impl Move {
+    /// Creates a move which has not yet left `from`
+    pub fn new<T: Into<Position>>(from: T) -> Self {
+        Move {
+            from: from.into(),
+            path: Vec::new(),
+            captured: Vec::new(),
+            promoted: false,
         }
     }
+
+    /// The square the piece finishes on
+    pub fn to(&self) -> Position {
+        *self.path.last().unwrap_or(&self.from)
+    }
+
+    pub fn is_capture(&self) -> bool {
+        !self.captured.is_empty()
+    }
+
+    /// Breaks the move down into the submoves it is made of
+    pub fn submoves(&self) -> Vec<Submove> {
+        let mut from = self.from;
+        self.path
+            .iter()
+            .map(|&to| {
+                let s = Submove { from, to };
+                from = to;
+                s
+            })
+            .collect()
+    }
 }
 
-pub type Moves = Vec<Submove>;
+/// Moves are written in standard notation, with the squares visited separated
+/// by `-` for a simple move or `x` for a jump, e.g. `11-15` or `15x24x31`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sep = match self.is_capture() {
+            true => "x",
+            _ => "-",
+        };
+        let squares: Vec<String> = Some(&self.from)
+            .into_iter()
+            .chain(self.path.iter())
+            .map(|p| match p.square() {
+                Some(n) => n.to_string(),
+                None => p.to_string(),
+            })
+            .collect();
+        write!(f, "{}", squares.join(sep))
+    }
+}
+
+pub type Moves = Vec<Move>;
+
+/// Reads the squares visited by a move written in standard notation, e.g.
+/// `11-15` or `15x22`.
+pub fn parse_squares(s: &str) -> Result<Vec<Position>, String> {
+    let squares = s
+        .trim()
+        .split(['-', 'x', 'X'])
+        .map(|n| {
+            n.trim()
+                .parse()
+                .ok()
+                .and_then(Position::from_square)
+                .ok_or_else(|| format!("'{}' is not a square.", n))
+        })
+        .collect::<Result<Vec<Position>, String>>()?;
+    match squares.len() {
+        0 | 1 => Err(format!("'{}' is not a move.", s.trim())),
+        _ => Ok(squares),
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn submove_add() {
-        let a = Submove::new((0, 0), (1, 1));
-        let b = Submove::new((1, 1), (0, 0));
-        let c = Submove::new((1, 1), (1, 1));
-        assert_eq!(a + b, c);
+    fn submove_delta() {
+        assert_eq!(Submove::new((5, 0), (3, 2)).delta(), (-2, 2));
+    }
+
+    #[test]
+    fn submove_norm() {
+        assert_eq!(
+            Submove::new((5, 0), (4, 1)).norm(Player::White),
+            Submove::new((2, 7), (3, 6))
+        );
     }
 
     #[test]
-    fn submove_sub() {
+    fn move_submoves() {
+        let mut m = Move::new((5, 0));
+        m.path = vec![Position(3, 2), Position(1, 4)];
+        assert_eq!(
+            m.submoves(),
+            vec![Submove::new((5, 0), (3, 2)), Submove::new((3, 2), (1, 4))]
+        );
+    }
+
+    #[test]
+    fn display_move() {
+        let mut m = Move::new((5, 2));
+        m.path.push(Position(4, 3));
+        assert_eq!(m.to_string(), "11-15");
+    }
+
+    #[test]
+    fn display_jump() {
+        let mut m = Move::new((4, 3));
+        m.path = vec![Position(2, 5), Position(0, 3)];
+        m.captured = vec![
+            (Position(3, 4), Piece::white()),
+            (Position(1, 4), Piece::white()),
+        ];
+        assert_eq!(m.to_string(), "15x22x31");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_squares("11-15"),
+            Ok(vec![Position(5, 2), Position(4, 3)])
+        );
+        assert_eq!(parse_squares(" 15x22x31 ").unwrap().len(), 3);
+        assert_eq!(
+            parse_squares("11"),
+            Err(String::from("'11' is not a move."))
+        );
+        assert_eq!(
+            parse_squares("11-33"),
+            Err(String::from("'33' is not a square."))
+        );
+    }
+
+    #[test]
+    fn serialize_json() {
+        let mut m = Move::new((4, 3));
+        m.path.push(Position(2, 5));
+        m.captured.push((Position(3, 4), Piece::white()));
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(
+            json,
+            r#"{"from":[4,3],"path":[[2,5]],"captured":[[[3,4],{"Normal":"White"}]],"promoted":false}"#
+        );
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), m);
         assert_eq!(
-            Submove::new((2, 2), (2, 2)) - Submove::new((1, 1), (1, 1)),
-            Submove::new((1, 1), (1, 1))
+            serde_json::to_string(&Submove::new((5, 0), (4, 1))).unwrap(),
+            r#"{"from":[5,0],"to":[4,1]}"#
         );
     }
 }
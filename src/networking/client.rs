@@ -1,49 +1,417 @@
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::str::from_utf8;
-use serde::{Serialize, Deserialize};
-
-use bincode::{serialize, deserialize};
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Point {
-    x: i32,
-    y: i32,
-}
-
-pub fn main() {
-    match TcpStream::connect("localhost:3333") {
-        Ok(mut stream) => {
-            println!("Successfully connected to server in port 3333");
-
-            // let msg = b"Hello!";
-            let msg = Point{ x: 1, y: 2};
-            // Convert the Point to a bincode.
-            let serialized = serialize(&msg).unwrap();
-            println!("Serialized Point: {:?}", serialized.as_slice());
-            stream.write(serialized.as_slice()).unwrap();
-            println!("Sent Hello, awaiting reply...");
-
-            let mut data = [0 as u8; 8]; // using 8 byte buffer
-            match stream.read_exact(&mut data) {
-                Ok(_) => {
-                    if &data == serialized.as_slice() {
-                        println!("Reply is ok!");
-                        let decoded: Point = deserialize(&data).unwrap();
-                        println!("Reply is...{:?}", decoded);
-                    } else {
-                        // let text = from_utf8(&data).unwrap();
-                        println!("Unexpected reply: {:?}", data);
+//! Connecting to a server, and a plain text client for playing a networked
+//! game from the terminal
+
+use crate::game::Game;
+use crate::networking::protocol::{self, Message, Token, HEARTBEAT, TIMEOUT, VERSION};
+use crate::networking::Settings;
+use crate::player::Player;
+
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The way to the other player of a networked game. A front-end may play
+/// through one without knowing whether there is a server in between: it
+/// sends requests to play and is told by the other end what happened.
+pub trait Link {
+    fn send(&mut self, m: &Message) -> io::Result<()>;
+    /// Returns the next message if one has arrived, without waiting for one
+    fn poll(&mut self) -> Option<io::Result<Message>>;
+    /// The address of the machine at the other end
+    fn peer_addr(&self) -> SocketAddr;
+    /// Connects again after the connection has dropped, to resume a game in
+    /// progress. Returns `false` if there is no game to resume.
+    fn reconnect(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+/// A connection to a server which is read on a background thread, so that
+/// it may be polled from an interface which cannot block waiting for it.
+///
+/// Heartbeats are sent and answered in the background too, so the other end
+/// keeps hearing from us however long the interface goes without polling.
+/// If it stops hearing from the other end for `TIMEOUT` the connection is
+/// given up, and polling it returns the error.
+pub struct Connection {
+    /// Kept to shut the connection down without waiting to write to it
+    stream: TcpStream,
+    writer: Arc<Mutex<TcpStream>>,
+    incoming: Receiver<io::Result<Message>>,
+    /// Messages which have been put back to be polled again
+    unread: VecDeque<Message>,
+    /// The token for the seat we were last given
+    session: Option<Token>,
+    /// The address of the server
+    pub peer: SocketAddr,
+}
+
+impl Connection {
+    /// Connects to a server and introduces ourselves as `name`
+    pub fn open<A: ToSocketAddrs>(addr: A, name: &str) -> io::Result<Connection> {
+        let mut conn = Connection::new(TcpStream::connect(addr)?)?;
+        conn.send(&Message::Hello {
+            version: VERSION,
+            name: String::from(name),
+        })?;
+        Ok(conn)
+    }
+
+    /// Starts reading messages from an established connection
+    pub fn new(stream: TcpStream) -> io::Result<Connection> {
+        let peer = stream.peer_addr()?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut reader = stream.try_clone()?;
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+
+        let (tx, rx) = channel();
+        let replies = Arc::clone(&writer);
+        thread::spawn(move || loop {
+            let m = match protocol::recv(&mut reader) {
+                Ok(Message::Ping(n)) => match write(&replies, &Message::Pong(n)) {
+                    Ok(()) => continue,
+                    Err(e) => Err(e),
+                },
+                Ok(Message::Pong(_)) => continue,
+                m => m,
+            };
+            let closed = m.is_err();
+            if tx.send(m).is_err() || closed {
+                break;
+            }
+        });
+        let pings = Arc::clone(&writer);
+        thread::spawn(move || heartbeat(&pings));
+
+        Ok(Connection {
+            stream,
+            writer,
+            incoming: rx,
+            unread: VecDeque::new(),
+            session: None,
+            peer,
+        })
+    }
+
+    pub fn send(&mut self, m: &Message) -> io::Result<()> {
+        write(&self.writer, m)
+    }
+
+    /// Returns the next message if one has arrived, without waiting for one.
+    /// Once the connection has failed its error is returned, and after that
+    /// there is nothing more to read.
+    pub fn poll(&mut self) -> Option<io::Result<Message>> {
+        if let Some(m) = self.unread.pop_front() {
+            return Some(Ok(m));
+        }
+        match self.incoming.try_recv() {
+            Ok(Ok(Message::Session(token))) => {
+                self.session = Some(token);
+                Some(Ok(Message::Session(token)))
+            }
+            Ok(m) => Some(m),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Connects to the server again and resumes the seat we were last given,
+    /// returning `false` if we have never been seated
+    pub fn reconnect(&mut self) -> io::Result<bool> {
+        let token = match self.session {
+            Some(token) => token,
+            None => return Ok(false),
+        };
+        let stream = TcpStream::connect_timeout(&self.peer, HEARTBEAT)?;
+        let mut conn = Connection::new(stream)?;
+        conn.session = Some(token);
+        conn.send(&Message::Resume {
+            version: VERSION,
+            token,
+        })?;
+        *self = conn;
+        Ok(true)
+    }
+
+    /// Puts back a message which has been polled but not handled, so that
+    /// it is polled again before anything else
+    pub fn unread(&mut self, m: Message) {
+        self.unread.push_back(m);
+    }
+}
+
+/// Writes a message on a connection shared between threads, so that whole
+/// messages are written one after the other
+fn write(writer: &Mutex<TcpStream>, m: &Message) -> io::Result<()> {
+    let mut stream = match writer.lock() {
+        Ok(stream) => stream,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    protocol::send(&mut *stream, m)
+}
+
+/// Pings the other end of a connection every `HEARTBEAT` until it closes
+fn heartbeat(writer: &Mutex<TcpStream>) {
+    let mut n = 0;
+    loop {
+        thread::sleep(HEARTBEAT);
+        n += 1;
+        if write(writer, &Message::Ping(n)).is_err() {
+            break;
+        }
+    }
+}
+
+impl Link for Connection {
+    fn send(&mut self, m: &Message) -> io::Result<()> {
+        Connection::send(self, m)
+    }
+
+    fn poll(&mut self) -> Option<io::Result<Message>> {
+        Connection::poll(self)
+    }
+
+    fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    fn reconnect(&mut self) -> io::Result<bool> {
+        Connection::reconnect(self)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Input from either the server or the person at the terminal
+enum Input {
+    Net(io::Result<Message>),
+    Line(String),
+}
+
+/// Reads messages from the server until the connection closes
+fn read_server(mut stream: TcpStream, input: Sender<Input>) {
+    loop {
+        let m = protocol::recv(&mut stream);
+        let closed = m.is_err();
+        if input.send(Input::Net(m)).is_err() || closed {
+            break;
+        }
+    }
+}
+
+fn read_stdin(input: Sender<Input>) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if input.send(Input::Line(line)).is_err() {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+fn prompt() {
+    print!("Your move (e.g. 11-15, draw, resign, or say <message>): ");
+    let _ = io::stdout().flush();
+}
+
+fn lobby_prompt() {
+    print!("Find a game (list, quick, new [black|white], join <number>, or watch <number>): ");
+    let _ = io::stdout().flush();
+}
+
+/// Reads a request typed in the lobby
+fn lobby_command(line: &str) -> Result<Message, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["list"] => Ok(Message::ListGames),
+        ["quick"] => Ok(Message::QuickMatch),
+        ["new"] => Ok(Message::CreateGame(None)),
+        ["new", "black"] => Ok(Message::CreateGame(Some(Player::Black))),
+        ["new", "white"] => Ok(Message::CreateGame(Some(Player::White))),
+        ["join", n] => n
+            .parse()
+            .map(Message::JoinGame)
+            .map_err(|_| format!("'{}' is not a game number.", n)),
+        ["watch", n] => n
+            .parse()
+            .map(Message::Watch)
+            .map_err(|_| format!("'{}' is not a game number.", n)),
+        _ => Err(format!("'{}' is not something you can do here.", line)),
+    }
+}
+
+/// Plays a game on an established connection until it is over
+fn play(mut stream: TcpStream, name: String) -> io::Result<()> {
+    // The server pings us every `HEARTBEAT`, so a longer silence means it
+    // has gone
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let (tx, rx) = channel();
+    let reader = stream.try_clone()?;
+    let net = tx.clone();
+    thread::spawn(move || read_server(reader, net));
+    thread::spawn(move || read_stdin(tx));
+
+    protocol::send(
+        &mut stream,
+        &Message::Hello {
+            version: VERSION,
+            name,
+        },
+    )?;
+
+    let mut game = Game::new();
+    let mut seat: Option<Player> = None;
+    let mut watching = false;
+    // Whether a move has been sent and not yet answered
+    let mut pending = false;
+
+    for input in rx {
+        match input {
+            Input::Net(m) => match m? {
+                Message::Games { open, running } => {
+                    if open.is_empty() {
+                        println!("No games are open.");
+                    }
+                    for g in open {
+                        match g.color {
+                            Some(p) => println!("{}: {} playing {:?}", g.id, g.host, p),
+                            None => println!("{}: {}", g.id, g.host),
+                        }
+                    }
+                    for g in running {
+                        println!("{}: {} v. {} (in progress)", g.id, g.black, g.white);
+                    }
+                    lobby_prompt();
+                }
+                Message::Watching { black, white } => {
+                    watching = true;
+                    println!("Watching {} v. {}.", black, white);
+                }
+                Message::GameCreated(id) => {
+                    println!("Opened game {}. Waiting for someone to join...", id);
+                }
+                Message::Refused(reason) => {
+                    println!("{}", reason);
+                    if seat.is_none() && !watching {
+                        lobby_prompt();
+                    }
+                }
+                Message::SeatAssigned { player, opponent } => {
+                    seat = Some(player);
+                    println!("Playing {:?} against {}.", player, opponent);
+                }
+                Message::MoveAccepted(m) => {
+                    pending = false;
+                    println!("{:?} played {}", game.player, m);
+                }
+                Message::State(state) => {
+                    game = *state;
+                    println!("{}", game.board);
+                    if seat == Some(game.player) {
+                        prompt();
+                    }
+                }
+                Message::MoveRejected(reason) => {
+                    pending = false;
+                    println!("{}", reason);
+                    prompt();
+                }
+                Message::GameOver { winner, reason } => {
+                    match winner {
+                        Some(p) => println!("{} {:?} wins.", reason, p),
+                        None => println!("{} The game is drawn.", reason),
                     }
+                    return Ok(());
+                }
+                Message::DrawOffer => println!("A draw is offered. Type 'draw' to accept."),
+                Message::PlayerAway { player, grace_ms } => println!(
+                    "{} has lost their connection, and has {} seconds to come back.",
+                    game.name(player),
+                    grace_ms / 1000
+                ),
+                Message::PlayerBack(player) => println!("{} is back.", game.name(player)),
+                Message::ChatMessage { from, text } => println!("{}: {}", from, text),
+                Message::Ping(n) => protocol::send(&mut stream, &Message::Pong(n))?,
+                _ => (),
+            },
+            Input::Line(_) if watching => println!("You are only watching this game."),
+            Input::Line(ref line) if seat.is_none() => match lobby_command(line) {
+                Ok(Message::QuickMatch) => {
+                    protocol::send(&mut stream, &Message::QuickMatch)?;
+                    println!("Waiting for an opponent...");
                 }
+                Ok(m) => protocol::send(&mut stream, &m)?,
                 Err(e) => {
-                    println!("Failed to receive data: {}", e);
+                    println!("{}", e);
+                    lobby_prompt();
                 }
-            }
-        }
-        Err(e) => {
-            println!("Failed to connect: {}", e);
+            },
+            Input::Line(line) => match line.trim() {
+                "resign" => protocol::send(&mut stream, &Message::Resign)?,
+                "draw" => protocol::send(&mut stream, &Message::DrawOffer)?,
+                s if s.starts_with("say ") => {
+                    let chat = Message::Chat(String::from(&s[4..]));
+                    protocol::send(&mut stream, &chat)?;
+                }
+                _ if seat != Some(game.player) || pending => {
+                    println!("Please wait for your turn.");
+                }
+                s => match game.parse_move(s) {
+                    Ok(m) => {
+                        protocol::send(&mut stream, &Message::MoveRequest(m))?;
+                        pending = true;
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        prompt();
+                    }
+                },
+            },
         }
     }
+    Ok(())
+}
+
+/// Connects to the server `settings` name and plays a game on it
+pub fn main(settings: &Settings) -> Result<(), String> {
+    let (host, port) = settings.host_addr();
+    let stream = TcpStream::connect((host, port))
+        .map_err(|e| format!("Could not connect to {}:{}: {}", host, port, e))?;
+    println!("Connected to {}:{}", host, port);
+    let name = env::var("USER").unwrap_or_else(|_| String::from("Anonymous"));
+    play(stream, name).map_err(|e| format!("Connection lost: {}", e))?;
     println!("Terminated.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lobby_commands() {
+        assert_eq!(lobby_command("quick"), Ok(Message::QuickMatch));
+        assert_eq!(
+            lobby_command(" new  white "),
+            Ok(Message::CreateGame(Some(Player::White)))
+        );
+        assert_eq!(lobby_command("join 4"), Ok(Message::JoinGame(4)));
+        assert_eq!(lobby_command("watch 2"), Ok(Message::Watch(2)));
+        assert!(lobby_command("join four").is_err());
+        assert!(lobby_command("dance").is_err());
+    }
 }
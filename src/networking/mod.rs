@@ -1,2 +1,137 @@
+//! Playing games over the network
+//!
+//! Where to listen for or connect to games is given by
+//! [`Settings`](./struct.Settings.html), which may come from the command line
+//! or the environment.
+
 pub mod client;
+pub mod peer;
+pub mod protocol;
 pub mod server;
+
+use std::env;
+
+/// The port games are played on unless another is chosen
+pub const DEFAULT_PORT: u16 = 3333;
+/// The address a server or host listens on unless another is chosen
+pub const DEFAULT_BIND: &str = "0.0.0.0";
+/// The server a client connects to unless another is chosen
+pub const DEFAULT_HOST: &str = "localhost";
+
+/// Where to listen for or connect to games. Anything not given explicitly is
+/// read from the `RUSTEDCHECKERS_BIND`, `RUSTEDCHECKERS_HOST` and
+/// `RUSTEDCHECKERS_PORT` environment variables, and failing that a default is
+/// used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The address to listen on
+    pub bind: String,
+    /// The server to connect to
+    pub host: String,
+    pub port: u16,
+}
+
+impl Settings {
+    pub fn new(
+        bind: Option<String>,
+        host: Option<String>,
+        port: Option<String>,
+    ) -> Result<Settings, String> {
+        Settings::with_env(bind, host, port, |var| env::var(var).ok())
+    }
+
+    /// Like `new`, but reads the environment through `var`
+    fn with_env<F>(
+        bind: Option<String>,
+        host: Option<String>,
+        port: Option<String>,
+        var: F,
+    ) -> Result<Settings, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let port = match port.or_else(|| var("RUSTEDCHECKERS_PORT")) {
+            Some(port) => port
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| format!("'{}' is not a port.", port))?,
+            None => DEFAULT_PORT,
+        };
+        Ok(Settings {
+            bind: bind
+                .or_else(|| var("RUSTEDCHECKERS_BIND"))
+                .unwrap_or_else(|| String::from(DEFAULT_BIND)),
+            host: host
+                .or_else(|| var("RUSTEDCHECKERS_HOST"))
+                .unwrap_or_else(|| String::from(DEFAULT_HOST)),
+            port,
+        })
+    }
+
+    /// The address to listen on, including the port
+    pub fn bind_addr(&self) -> (&str, u16) {
+        (&self.bind, self.port)
+    }
+
+    /// The address of the server, including the port
+    pub fn host_addr(&self) -> (&str, u16) {
+        (&self.host, self.port)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            bind: String::from(DEFAULT_BIND),
+            host: String::from(DEFAULT_HOST),
+            port: DEFAULT_PORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    fn env(var: &str) -> Option<String> {
+        match var {
+            "RUSTEDCHECKERS_HOST" => Some(String::from("checkers.example.com")),
+            "RUSTEDCHECKERS_PORT" => Some(String::from("4444")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let settings = Settings::with_env(None, None, None, no_env).unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn from_env() {
+        let settings = Settings::with_env(None, None, None, env).unwrap();
+        assert_eq!(settings.bind, DEFAULT_BIND);
+        assert_eq!(settings.host_addr(), ("checkers.example.com", 4444));
+    }
+
+    #[test]
+    fn flags_override_env() {
+        let host = Some(String::from("127.0.0.1"));
+        let port = Some(String::from("5555"));
+        let settings = Settings::with_env(None, host, port, env).unwrap();
+        assert_eq!(settings.host_addr(), ("127.0.0.1", 5555));
+    }
+
+    #[test]
+    fn bad_port() {
+        let port = Some(String::from("99999"));
+        assert_eq!(
+            Settings::with_env(None, None, port, no_env),
+            Err(String::from("'99999' is not a port."))
+        );
+    }
+}
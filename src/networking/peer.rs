@@ -0,0 +1,402 @@
+//! Playing directly against another player, without a server
+//!
+//! One peer hosts a game by listening for the other to join it. Once they
+//! have said hello and agreed who plays which colour, each keeps its own copy
+//! of the game. A turn is checked by the player making it before it is sent,
+//! and again by the opponent, who only plays it on their copy of the game if
+//! it is legal and says so. The mover plays it once the opponent has agreed.
+//!
+//! The host decides whether the game is timed. Each peer times its own
+//! turns and tells the other how long each took, so that both keep the same
+//! clock, though a turn is never taken to have been much quicker than the
+//! other peer saw it to be. Either peer may call the game once the player
+//! to move has run out of time, but the other only agrees if its own clock
+//! says so too.
+//!
+//! To the front-end a [`Peer`](./struct.Peer.html) looks just like a
+//! connection to a server: it is sent requests to play and answers with the
+//! same messages a server would.
+
+use crate::clock::{Clock, TimeControl};
+use crate::game::Game;
+use crate::moves::Move;
+use crate::networking::client::{Connection, Link};
+use crate::networking::protocol::{self, Message, Rejection, VERSION};
+use crate::player::Player;
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the other peer to answer while agreeing to play
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much less time than we saw the other peer take over a turn they may
+/// say it took, allowing for their turn taking a while to reach us
+const LATENCY_ALLOWANCE: Duration = Duration::from_millis(500);
+
+/// A game played directly against another peer
+pub struct Peer {
+    conn: Connection,
+    /// Our colour
+    pub seat: Player,
+    /// The game as both peers have agreed it to be
+    pub game: Game,
+    /// A turn we have sent which the opponent has yet to agree to, and how
+    /// long it took
+    pending: Option<(Move, Duration)>,
+    /// How long the opponent says their next turn took
+    time_used: Option<Duration>,
+    /// When the player to move started their turn
+    turn_started: Instant,
+    /// The player who has offered a draw which has not yet been answered
+    draw_offer: Option<Player>,
+    over: bool,
+    /// Messages for the front-end which have not yet been polled
+    outbox: VecDeque<Message>,
+}
+
+fn invalid(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Reads the other peer's hello, returning their name
+fn recv_hello(stream: &mut TcpStream) -> io::Result<String> {
+    match protocol::recv(stream)? {
+        Message::Hello { version, name } => {
+            if version != VERSION {
+                let reason = format!(
+                    "The other player speaks protocol version {}, not {}.",
+                    version, VERSION
+                );
+                let over = Message::GameOver {
+                    winner: None,
+                    reason: reason.clone(),
+                };
+                let _ = protocol::send(stream, &over);
+                return Err(invalid(reason));
+            }
+            Ok(name)
+        }
+        Message::GameOver { reason, .. } => Err(invalid(reason)),
+        m => Err(invalid(format!("Expected a hello, not {:?}", m))),
+    }
+}
+
+fn send_hello(stream: &mut TcpStream, name: &str) -> io::Result<()> {
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from(name),
+    };
+    protocol::send(stream, &hello)
+}
+
+/// Decides the host's colour. The host's preference wins, then the guest's,
+/// and if neither minds the host plays black.
+fn host_color(host: Option<Player>, guest: Option<Player>) -> Player {
+    match (host, guest) {
+        (Some(p), _) => p,
+        (None, Some(p)) => p.switch(),
+        (None, None) => Player::Black,
+    }
+}
+
+impl Peer {
+    /// Waits for another peer to join a game on `listener`, playing `color`
+    /// if given, and timed by `time` if given. If `listener` is non-blocking
+    /// and no one is waiting to join, fails with `WouldBlock` at once.
+    pub fn host(
+        listener: &TcpListener,
+        name: &str,
+        color: Option<Player>,
+        time: Option<TimeControl>,
+    ) -> io::Result<Peer> {
+        let (mut stream, _) = listener.accept()?;
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let opponent = recv_hello(&mut stream)?;
+        send_hello(&mut stream, name)?;
+        let seat = match protocol::recv(&mut stream)? {
+            Message::ColorPreference(p) => host_color(color, p),
+            m => return Err(invalid(format!("Expected a colour, not {:?}", m))),
+        };
+        let assigned = Message::SeatAssigned {
+            player: seat.switch(),
+            opponent: String::from(name),
+        };
+        protocol::send(&mut stream, &assigned)?;
+
+        let mut game = Game::new();
+        match seat {
+            Player::Black => {
+                game.black_name = String::from(name);
+                game.white_name = opponent;
+            }
+            Player::White => {
+                game.black_name = opponent;
+                game.white_name = String::from(name);
+            }
+        }
+        game.clock = time.map(Clock::new);
+        protocol::send(&mut stream, &Message::State(Box::new(game.clone())))?;
+        stream.set_read_timeout(None)?;
+        Peer::new(stream, seat, game)
+    }
+
+    /// Joins the game hosted at `addr`, asking to play `color` if given
+    pub fn join<A: ToSocketAddrs>(addr: A, name: &str, color: Option<Player>) -> io::Result<Peer> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        send_hello(&mut stream, name)?;
+        let opponent = recv_hello(&mut stream)?;
+        protocol::send(&mut stream, &Message::ColorPreference(color))?;
+        let seat = match protocol::recv(&mut stream)? {
+            Message::SeatAssigned { player, .. } => player,
+            m => return Err(invalid(format!("Expected a seat, not {:?}", m))),
+        };
+        // The host starts the game, so that both agree on the clock
+        let game = match protocol::recv(&mut stream)? {
+            Message::State(game) => *game,
+            m => return Err(invalid(format!("Expected the game, not {:?}", m))),
+        };
+        if game.name(seat) != name || game.name(seat.switch()) != opponent {
+            return Err(invalid(String::from("The host started a different game.")));
+        }
+        stream.set_read_timeout(None)?;
+        Peer::new(stream, seat, game)
+    }
+
+    fn new(stream: TcpStream, seat: Player, game: Game) -> io::Result<Peer> {
+        let mut outbox = VecDeque::new();
+        outbox.push_back(Message::SeatAssigned {
+            player: seat,
+            opponent: String::from(game.name(seat.switch())),
+        });
+        outbox.push_back(Message::State(Box::new(game.clone())));
+        Ok(Peer {
+            conn: Connection::new(stream)?,
+            seat,
+            game,
+            pending: None,
+            time_used: None,
+            turn_started: Instant::now(),
+            draw_offer: None,
+            over: false,
+            outbox,
+        })
+    }
+
+    /// Ends the game, telling the front-end the result
+    fn finish(&mut self, winner: Option<Player>, reason: String) {
+        self.over = true;
+        self.outbox.push_back(Message::GameOver { winner, reason });
+    }
+
+    /// Plays a turn both peers have agreed to, taking the time it took off
+    /// the mover's clock
+    fn played(&mut self, m: Move, mover: Player, took: Duration) {
+        let in_time = match self.game.clock.as_mut() {
+            Some(clock) => clock.press(mover, took),
+            None => true,
+        };
+        self.turn_started = Instant::now();
+        self.draw_offer = None;
+        self.outbox.push_back(Message::MoveAccepted(m));
+        self.outbox
+            .push_back(Message::State(Box::new(self.game.clone())));
+        if !in_time {
+            let reason = format!("{} ran out of time.", self.game.name(mover));
+            self.finish(Some(mover.switch()), reason);
+        } else if let Some(winner) = self.game.win_condition() {
+            let reason = format!("{} cannot move.", self.game.name(winner.switch()));
+            self.finish(Some(winner), reason);
+        }
+    }
+
+    /// Handles a message from the front-end
+    fn local(&mut self, m: &Message) -> io::Result<()> {
+        if let Message::Chat(text) = m {
+            match protocol::chat_text(text) {
+                Ok(text) => {
+                    self.conn.send(&Message::Chat(text.clone()))?;
+                    let from = String::from(self.game.name(self.seat));
+                    self.outbox.push_back(Message::ChatMessage { from, text });
+                }
+                Err(reason) => self.outbox.push_back(Message::Refused(reason)),
+            }
+            return Ok(());
+        }
+        if self.over {
+            return Ok(());
+        }
+        match m {
+            Message::MoveRequest(_) if self.game.player != self.seat || self.pending.is_some() => {
+                let rejected = Message::MoveRejected(Rejection::NotYourTurn);
+                self.outbox.push_back(rejected);
+            }
+            Message::MoveRequest(mv) => match self.game.clone().do_move(mv) {
+                Ok(_) => {
+                    let took = self.turn_started.elapsed();
+                    self.pending = Some((mv.clone(), took));
+                    if self.game.clock.is_some() {
+                        self.conn.send(&Message::TimeUsed(millis(took)))?;
+                    }
+                    self.conn.send(m)?;
+                }
+                Err(e) => {
+                    let rejected = Message::MoveRejected(Rejection::Illegal(e));
+                    self.outbox.push_back(rejected);
+                }
+            },
+            Message::Resign => {
+                self.conn.send(m)?;
+                let reason = format!("{} resigned.", self.game.name(self.seat));
+                self.finish(Some(self.seat.switch()), reason);
+            }
+            Message::DrawOffer => {
+                self.conn.send(m)?;
+                if self.draw_offer == Some(self.seat.switch()) {
+                    self.finish(None, String::from("Draw agreed."));
+                } else {
+                    self.draw_offer = Some(self.seat);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Handles a message from the other peer
+    fn remote(&mut self, m: Message) -> io::Result<()> {
+        let opponent = self.seat.switch();
+        match m {
+            Message::MoveRequest(_) if self.over || self.game.player != opponent => {
+                let rejected = Message::MoveRejected(Rejection::NotYourTurn);
+                self.conn.send(&rejected)?;
+            }
+            Message::MoveRequest(mv) => match self.game.do_move(&mv) {
+                Ok(_) => {
+                    self.conn.send(&Message::MoveAccepted(mv.clone()))?;
+                    let seen = self.turn_started.elapsed();
+                    let took = match self.time_used.take() {
+                        Some(took) => took.max(seen.saturating_sub(LATENCY_ALLOWANCE)),
+                        None => seen,
+                    };
+                    self.played(mv, opponent, took);
+                }
+                Err(e) => {
+                    let rejected = Message::MoveRejected(Rejection::Illegal(e));
+                    self.conn.send(&rejected)?;
+                }
+            },
+            Message::TimeUsed(ms) => self.time_used = Some(Duration::from_millis(ms)),
+            Message::MoveAccepted(mv) => {
+                let took = match &self.pending {
+                    Some((pending, took)) if *pending == mv => *took,
+                    _ => return Ok(()),
+                };
+                if self.game.do_move(&mv).is_ok() {
+                    self.pending = None;
+                    self.played(mv, self.seat, took);
+                }
+            }
+            Message::MoveRejected(reason) => {
+                self.pending = None;
+                self.outbox.push_back(Message::MoveRejected(reason));
+            }
+            Message::Resign if !self.over => {
+                let reason = format!("{} resigned.", self.game.name(opponent));
+                self.finish(Some(self.seat), reason);
+            }
+            Message::DrawOffer if !self.over => {
+                if self.draw_offer == Some(self.seat) {
+                    self.finish(None, String::from("Draw agreed."));
+                } else {
+                    self.draw_offer = Some(opponent);
+                    self.outbox.push_back(Message::DrawOffer);
+                }
+            }
+            // The only result the other peer may call is a loss on time,
+            // and only once our own clock agrees
+            Message::GameOver { winner, .. } if !self.over => {
+                let loser = self.game.player;
+                if winner == Some(loser.switch())
+                    && self.game.out_of_time(self.turn_started.elapsed())
+                {
+                    let reason = format!("{} ran out of time.", self.game.name(loser));
+                    self.finish(winner, reason);
+                }
+            }
+            Message::Chat(text) => {
+                if let Ok(text) = protocol::chat_text(&text) {
+                    let from = String::from(self.game.name(opponent));
+                    self.outbox.push_back(Message::ChatMessage { from, text });
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Ends the game if the player to move has run out of time, telling the
+    /// other peer too
+    fn check_time(&mut self) -> io::Result<()> {
+        if self.over || !self.game.out_of_time(self.turn_started.elapsed()) {
+            return Ok(());
+        }
+        let loser = self.game.player;
+        let winner = Some(loser.switch());
+        let reason = format!("{} ran out of time.", self.game.name(loser));
+        self.finish(winner, reason.clone());
+        self.conn.send(&Message::GameOver { winner, reason })
+    }
+}
+
+/// A duration in whole milliseconds
+fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + u64::from(d.subsec_millis())
+}
+
+impl Link for Peer {
+    fn send(&mut self, m: &Message) -> io::Result<()> {
+        self.local(m)
+    }
+
+    fn poll(&mut self) -> Option<io::Result<Message>> {
+        if let Err(e) = self.check_time() {
+            return Some(Err(e));
+        }
+        while self.outbox.is_empty() {
+            match self.conn.poll()? {
+                Ok(m) => {
+                    if let Err(e) = self.remote(m) {
+                        return Some(Err(e));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.outbox.pop_front().map(Ok)
+    }
+
+    fn peer_addr(&self) -> SocketAddr {
+        self.conn.peer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!(host_color(Some(Player::White), None), Player::White);
+        assert_eq!(
+            host_color(Some(Player::White), Some(Player::White)),
+            Player::White
+        );
+        assert_eq!(host_color(None, Some(Player::Black)), Player::White);
+        assert_eq!(host_color(None, None), Player::Black);
+    }
+}
@@ -0,0 +1,322 @@
+//! The messages exchanged between clients and the server, or directly
+//! between two peers
+//!
+//! Every message is framed as a 4 byte big-endian length followed by that
+//! many bytes of the bincode serialization of a [`Message`](./enum.Message.html).
+
+use crate::game::Game;
+use crate::moves::Move;
+use crate::player::Player;
+
+use bincode::{deserialize, serialize};
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// The version of the protocol. A client must announce the same version in
+/// its `Hello` to be seated.
+pub const VERSION: u32 = 8;
+
+/// The largest message, in bytes, which will be accepted
+pub const MAX_MESSAGE_LEN: u32 = 64 * 1024;
+
+/// How often each end of a connection pings the other, so that a connection
+/// which is only quiet is not mistaken for a dead one
+pub const HEARTBEAT: Duration = Duration::from_secs(10);
+
+/// How long a connection may go without hearing from the other end, or
+/// wait to write to it, before it is given up for dead
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The longest chat message, in characters, which may be sent
+pub const MAX_CHAT_LEN: usize = 200;
+
+/// The number a server gives each game it hosts
+pub type GameId = u64;
+
+/// A secret given to a seated player, with which they may take their seat
+/// again after their connection drops
+pub type Token = u64;
+
+/// A game on a server which is waiting for an opponent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenGame {
+    pub id: GameId,
+    /// The nickname of the player who opened it
+    pub host: String,
+    /// The colour the host will play, if they mind
+    pub color: Option<Player>,
+}
+
+/// A game on a server which is being played, and may be watched
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunningGame {
+    pub id: GameId,
+    pub black: String,
+    pub white: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Sent by a client upon connecting. A server registers the client under
+    /// `name`, which must not already be in use.
+    Hello {
+        version: u32,
+        name: String,
+    },
+    /// Sent by a client upon reconnecting, instead of `Hello`, to take back
+    /// its seat in a game
+    Resume {
+        version: u32,
+        token: Token,
+    },
+    /// Sent by a client in the lobby of a server to see its games
+    ListGames,
+    /// Sent by the server once a client has registered, and in answer to
+    /// `ListGames`
+    Games {
+        open: Vec<OpenGame>,
+        running: Vec<RunningGame>,
+    },
+    /// Sent by a client to open a game and wait for someone to join it,
+    /// playing the given colour if any
+    CreateGame(Option<Player>),
+    /// Sent by the server once a client's game has been opened
+    GameCreated(GameId),
+    /// Sent by a client to join an open game
+    JoinGame(GameId),
+    /// Sent by a client to be paired with whoever else asks next
+    QuickMatch,
+    /// Sent by a client to watch a running game without playing in it
+    Watch(GameId),
+    /// Sent by the server to a client which has started watching a game. The
+    /// state of the game follows, and then every turn played in it until it
+    /// is over.
+    Watching {
+        black: String,
+        white: String,
+    },
+    /// Sent by the server when a request from the lobby cannot be granted
+    Refused(String),
+    /// Sent by a peer joining a game to say which colour it would like to
+    /// play, if it minds
+    ColorPreference(Option<Player>),
+    /// Sent by the server once a client has been paired with an opponent,
+    /// or by a host to the peer which has joined it
+    SeatAssigned {
+        player: Player,
+        opponent: String,
+    },
+    /// Sent by the server to a player after seating them, with the token to
+    /// resume their seat with
+    Session(Token),
+    /// Sent by the server to everyone in a game when one of its players'
+    /// connection drops. They lose unless they resume their seat within
+    /// `grace_ms` milliseconds.
+    PlayerAway {
+        player: Player,
+        grace_ms: u64,
+    },
+    /// Sent by the server to everyone in a game when a player who was away
+    /// has resumed their seat
+    PlayerBack(Player),
+    /// Sent by a client to play a turn
+    MoveRequest(Move),
+    /// Sent by a peer just before its `MoveRequest` in a timed game, with
+    /// how many milliseconds it spent on the turn, so that both peers take
+    /// the same time off its clock
+    TimeUsed(u64),
+    /// Sent by the server to both players once a turn has been played
+    MoveAccepted(Move),
+    /// Sent by the server to a client whose turn could not be played
+    MoveRejected(Rejection),
+    /// Sent by the server to both players when they are seated and after
+    /// every turn. This is the authoritative state of the game, which
+    /// replaces whatever the client believes it to be. Its clock, if any, has
+    /// been read as it was sent.
+    State(Box<Game>),
+    /// Sent by the server to both players once the game has finished. There
+    /// is no winner of a drawn game.
+    GameOver {
+        winner: Option<Player>,
+        reason: String,
+    },
+    /// Sent by a client to concede the game
+    Resign,
+    /// Sent by a client to offer a draw, or to accept the opponent's offer.
+    /// The server passes the offer on to the opponent.
+    DrawOffer,
+    /// Sent by a player to say something to everyone in their game
+    Chat(String),
+    /// Sent by the server to everyone in a game when one of its players has
+    /// said something
+    ChatMessage {
+        from: String,
+        text: String,
+    },
+    /// Sent by either end every `HEARTBEAT`. It must be answered with a
+    /// `Pong` carrying the same number.
+    Ping(u64),
+    Pong(u64),
+}
+
+/// The reason a `MoveRequest` was rejected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The client is not seated in a game
+    NotSeated,
+    NotYourTurn,
+    /// The move breaks the rules of the game, as described
+    Illegal(String),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::NotSeated => write!(f, "You are not seated in a game."),
+            Rejection::NotYourTurn => write!(f, "It is not your turn."),
+            Rejection::Illegal(reason) => write!(f, "Illegal move: {}", reason),
+        }
+    }
+}
+
+/// Tidies text from another player for showing on a terminal. Control
+/// characters are dropped so they cannot upset the terminal it is shown on.
+pub fn printable(text: &str) -> String {
+    text.trim().chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Tidies chat text for showing to other players, or explains why it may
+/// not be sent
+pub fn chat_text(text: &str) -> Result<String, String> {
+    let text = printable(text);
+    if text.is_empty() {
+        Err(String::from("Chat messages may not be empty."))
+    } else if text.chars().count() > MAX_CHAT_LEN {
+        Err(format!(
+            "Chat messages may be at most {} characters long.",
+            MAX_CHAT_LEN
+        ))
+    } else {
+        Ok(text)
+    }
+}
+
+/// Writes a single framed message
+pub fn send<W: Write>(w: &mut W, m: &Message) -> io::Result<()> {
+    let bytes = serialize(m).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if bytes.len() as u64 > u64::from(MAX_MESSAGE_LEN) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Message of {} bytes is too long.", bytes.len()),
+        ));
+    }
+    w.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    w.write_all(&bytes)?;
+    w.flush()
+}
+
+/// Reads a single framed message, blocking until it has arrived
+pub fn recv<R: Read>(r: &mut R) -> io::Result<Message> {
+    let mut len = [0u8; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message of {} bytes is too long.", len),
+        ));
+    }
+    let mut data = vec![0u8; len as usize];
+    r.read_exact(&mut data)?;
+    deserialize(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Position;
+
+    #[test]
+    fn round_trip() {
+        let mut m = Move::new((5, 0));
+        m.path.push(Position::new(4, 1));
+        let messages = vec![
+            Message::Hello {
+                version: VERSION,
+                name: String::from("Eric"),
+            },
+            Message::MoveRequest(m),
+            Message::GameOver {
+                winner: None,
+                reason: String::from("Draw agreed"),
+            },
+            Message::MoveRejected(Rejection::Illegal(String::from(
+                "No piece exists at (0, 0).",
+            ))),
+            Message::State(Box::default()),
+            Message::ColorPreference(Some(Player::White)),
+            Message::Games {
+                open: vec![OpenGame {
+                    id: 3,
+                    host: String::from("Derek"),
+                    color: None,
+                }],
+                running: vec![RunningGame {
+                    id: 2,
+                    black: String::from("Eric"),
+                    white: String::from("David"),
+                }],
+            },
+            Message::JoinGame(3),
+            Message::Watch(2),
+            Message::Resume {
+                version: VERSION,
+                token: 0xdead_beef,
+            },
+            Message::PlayerAway {
+                player: Player::Black,
+                grace_ms: 60_000,
+            },
+            Message::Ping(7),
+            Message::ChatMessage {
+                from: String::from("Daniel"),
+                text: String::from("Good game!"),
+            },
+        ];
+
+        let mut buf = Vec::new();
+        for m in messages.iter() {
+            send(&mut buf, m).unwrap();
+        }
+        let mut r = buf.as_slice();
+        for m in messages.iter() {
+            assert_eq!(recv(&mut r).unwrap(), *m);
+        }
+        assert!(recv(&mut r).is_err());
+    }
+
+    #[test]
+    fn chat() {
+        assert_eq!(chat_text(" gg\u{7} "), Ok(String::from("gg")));
+        assert!(chat_text(" \t").is_err());
+        assert!(chat_text(&"a".repeat(MAX_CHAT_LEN)).is_ok());
+        assert!(chat_text(&"a".repeat(MAX_CHAT_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn framing() {
+        let mut buf = Vec::new();
+        send(&mut buf, &Message::Resign).unwrap();
+        let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        assert_eq!(len as usize, buf.len() - 4);
+    }
+
+    #[test]
+    fn too_long() {
+        let buf = (MAX_MESSAGE_LEN + 1).to_be_bytes();
+        let err = recv(&mut &buf[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
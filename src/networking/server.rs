@@ -1,46 +1,950 @@
-use std::io::{Read, Write};
+//! A server which hosts many games at once and referees each of them. The
+//! server holds the only authoritative copy of each game: every turn is
+//! checked against the rules before it is played, and the resulting state is
+//! sent to both players.
+//!
+//! Clients register a nickname with their `Hello`, and then find an opponent
+//! in the lobby. They may open a game and wait for someone to join it, join
+//! a game someone else has opened, or ask to be paired with whoever else asks
+//! next. They may also watch any game being played, receiving everything
+//! its players do but taking no part in it.
+//!
+//! A game belongs to the server rather than to its players' connections. A
+//! seated player is given a session token, and if their connection drops
+//! they have a grace period in which to reconnect and resume their seat with
+//! it before they lose. If neither player comes back the game is abandoned.
+//!
+//! The server pings every connection each heartbeat, and gives up on one it
+//! has not heard from for longer than its timeout. A connection whose other
+//! end has vanished without closing it is noticed that way.
+//!
+//! Games may be timed. The server keeps each game's clock, timing turns as
+//! it receives them, and a player who runs out of time loses. Whether a
+//! player's clock runs while they are away is up to the server.
+//!
+//! Players may chat with everyone in their game. So that nobody can flood
+//! it, messages are limited in length and in how many may be sent at once.
+//!
+//! Each connection is read on its own thread, which passes what it reads on
+//! to a single thread holding the state of every game. What that thread has
+//! to say is queued for another thread per connection to write, so that a
+//! client which is slow to read holds up nobody else.
+
+use crate::clock::{Clock, TimeControl};
+use crate::game::Game;
+use crate::networking::protocol::{
+    self, GameId, Message, OpenGame, Rejection, RunningGame, Token, HEARTBEAT, TIMEOUT, VERSION,
+};
+use crate::networking::Settings;
+use crate::player::Player;
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
 use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError,
+};
 use std::thread;
-use serde::{Serialize, Deserialize};
-
-fn handle_client(mut stream: TcpStream) {
-    let mut data = [0 as u8; 50]; // using 50 byte buffer
-    while match stream.read(&mut data) {
-        Ok(size) => {
-            // echo everything!
-            stream.write(&data[0..size]).unwrap();
-            true
-        }
-        Err(_) => {
-            println!(
-                "An error occurred, terminating connection with {}",
-                stream.peer_addr().unwrap()
+use std::time::{Duration, Instant};
+
+type ConnId = usize;
+type MatchId = GameId;
+
+/// The longest nickname a client may register
+const MAX_NAME_LEN: usize = 32;
+
+/// How many chat messages a client may send within `CHAT_WINDOW`
+const CHAT_BURST: usize = 5;
+const CHAT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How many messages may wait to be written to a connection before its
+/// client is taken to have stopped reading
+const OUTBOX_LEN: usize = 256;
+
+/// How often the server checks for connections which are due a ping, and for
+/// players who have been away too long
+const TICK: Duration = Duration::from_millis(100);
+
+/// How the server looks after its connections, and treats players whose
+/// connections drop
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How long a player has to resume their seat before they lose
+    pub grace: Duration,
+    /// Whether a player's clock runs while they are away
+    pub away_clock: AwayClock,
+    /// The time control games are played to, if they are timed
+    pub time: Option<TimeControl>,
+    /// How often each connection is pinged
+    pub heartbeat: Duration,
+    /// How long a connection may go unheard from, or take to be written
+    /// to, before it is closed. This should be a few heartbeats.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AwayClock {
+    Paused,
+    Running,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            grace: Duration::from_secs(60),
+            away_clock: AwayClock::Paused,
+            time: None,
+            heartbeat: HEARTBEAT,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+/// Something which happened on one of the server's connections
+enum Event {
+    Connected(ConnId, TcpStream, SyncSender<Message>),
+    Received(ConnId, Message),
+    Closed(ConnId),
+}
+
+struct Conn {
+    stream: TcpStream,
+    /// Messages for the connection's writer. Letting go of it closes the
+    /// connection once they have been written.
+    outbox: Option<SyncSender<Message>>,
+    /// The name given in the client's `Hello`
+    name: Option<String>,
+    seat: Option<(MatchId, Player)>,
+    /// The game the client is watching
+    watching: Option<MatchId>,
+    /// When the client was last pinged
+    pinged: Instant,
+    /// When the client sent its chat messages within the last `CHAT_WINDOW`
+    chatted: VecDeque<Instant>,
+}
+
+/// A game which has been opened and is waiting for someone to join it
+struct Open {
+    host: ConnId,
+    color: Option<Player>,
+}
+
+struct Match {
+    black: ConnId,
+    white: ConnId,
+    game: Game,
+    /// The player who has offered a draw which has not yet been answered
+    draw_offer: Option<Player>,
+    spectators: Vec<ConnId>,
+    /// The players whose connections have dropped, and since when
+    away: Vec<(Player, Instant)>,
+    /// When the player to move started their turn
+    turn_started: Instant,
+}
+
+impl Match {
+    fn conn(&self, p: Player) -> ConnId {
+        match p {
+            Player::Black => self.black,
+            Player::White => self.white,
+        }
+    }
+
+    fn set_conn(&mut self, p: Player, id: ConnId) {
+        match p {
+            Player::Black => self.black = id,
+            Player::White => self.white = id,
+        }
+    }
+
+    /// The game as it stands at `now`, with its clock read
+    fn state(&self, now: Instant) -> Game {
+        let mut game = self.game.clone();
+        let thinking = now.duration_since(self.turn_started);
+        game.clock = game.clock.map(|c| c.read(thinking));
+        game
+    }
+
+    /// The players and spectators of the game
+    fn audience(&self) -> Vec<ConnId> {
+        let mut ids = vec![self.black, self.white];
+        ids.extend(self.spectators.iter());
+        ids
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    conns: HashMap<ConnId, Conn>,
+    matches: HashMap<MatchId, Match>,
+    /// Games waiting for someone to join them, oldest first
+    open: BTreeMap<MatchId, Open>,
+    /// A client which has asked for a quick match and is waiting for another
+    waiting: Option<ConnId>,
+    next_match: MatchId,
+    /// The seat each session token resumes
+    sessions: HashMap<Token, (MatchId, Player)>,
+    /// The number of the last ping sent
+    pings: u64,
+    config: Config,
+}
+
+impl Server {
+    /// Queues a message for a connection, dropping the connection if its
+    /// client has stopped reading. The connection's reader will then report
+    /// it as closed.
+    fn send(&mut self, id: ConnId, m: &Message) {
+        if let Some(conn) = self.conns.get_mut(&id) {
+            let queued = match &conn.outbox {
+                Some(outbox) => outbox.try_send(m.clone()),
+                None => return,
+            };
+            if let Err(TrySendError::Full(_)) = queued {
+                println!("Connection {} is not keeping up", id);
+                let _ = conn.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    fn name(&self, id: ConnId) -> String {
+        self.conns
+            .get(&id)
+            .and_then(|c| c.name.clone())
+            .unwrap_or_default()
+    }
+
+    fn handle(&mut self, e: Event) {
+        match e {
+            Event::Connected(id, stream, outbox) => {
+                let conn = Conn {
+                    stream,
+                    outbox: Some(outbox),
+                    name: None,
+                    seat: None,
+                    watching: None,
+                    pinged: Instant::now(),
+                    chatted: VecDeque::new(),
+                };
+                self.conns.insert(id, conn);
+            }
+            Event::Received(id, m) => self.received(id, m),
+            Event::Closed(id) => self.closed(id),
+        }
+    }
+
+    fn received(&mut self, id: ConnId, m: Message) {
+        match m {
+            Message::Hello { version, name } => self.hello(id, version, name),
+            Message::Resume { version, token } => self.resume(id, version, token),
+            Message::Ping(n) => self.send(id, &Message::Pong(n)),
+            Message::ListGames
+            | Message::CreateGame(_)
+            | Message::JoinGame(_)
+            | Message::QuickMatch
+            | Message::Watch(_) => self.lobby(id, m),
+            Message::Chat(text) => self.chat(id, &text),
+            Message::MoveRequest(_) | Message::Resign | Message::DrawOffer => {
+                let seat = self.conns.get(&id).and_then(|c| c.seat);
+                match seat {
+                    Some((match_id, player)) => self.play(match_id, player, m),
+                    None => self.send(id, &Message::MoveRejected(Rejection::NotSeated)),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Checks a client speaks our version of the protocol, turning it away
+    /// if not
+    fn check_version(&mut self, id: ConnId, version: u32) -> bool {
+        if version == VERSION {
+            return true;
+        }
+        let reason = format!(
+            "The server speaks protocol version {}, not {}.",
+            VERSION, version
+        );
+        self.send(
+            id,
+            &Message::GameOver {
+                winner: None,
+                reason,
+            },
+        );
+        // The connection closes once it has been told why
+        if let Some(conn) = self.conns.get_mut(&id) {
+            conn.outbox = None;
+        }
+        false
+    }
+
+    fn hello(&mut self, id: ConnId, version: u32, name: String) {
+        if !self.check_version(id, version) {
+            return;
+        }
+
+        // Names are shown to other players just as chat is
+        let name = protocol::printable(&name);
+        let refusal = if name.is_empty() {
+            Some(String::from("Nicknames may not be empty."))
+        } else if name.chars().count() > MAX_NAME_LEN {
+            Some(format!(
+                "Nicknames may be at most {} characters long.",
+                MAX_NAME_LEN
+            ))
+        } else if self
+            .conns
+            .values()
+            .any(|c| c.name.as_deref() == Some(name.as_str()))
+        {
+            Some(format!("The nickname {} is taken.", name))
+        } else {
+            None
+        };
+        match self.conns.get_mut(&id) {
+            Some(conn) if conn.name.is_none() => match refusal {
+                Some(reason) => return self.send(id, &Message::Refused(reason)),
+                None => conn.name = Some(name.clone()),
+            },
+            _ => return,
+        }
+        println!("Connection {} registered as {}", id, name);
+        self.send_games(id);
+    }
+
+    /// Seats a client in place of a player who has reconnected
+    fn resume(&mut self, id: ConnId, version: u32, token: Token) {
+        if !self.check_version(id, version) {
+            return;
+        }
+        match self.conns.get(&id) {
+            Some(conn) if conn.name.is_none() => (),
+            _ => return,
+        }
+        let (match_id, player) = match self.sessions.get(&token) {
+            Some(&seat) => seat,
+            None => {
+                let reason = String::from("There is no game to resume.");
+                return self.send(id, &Message::Refused(reason));
+            }
+        };
+        let now = Instant::now();
+        let pause = self.config.away_clock == AwayClock::Paused;
+        let (old, game, paused) = match self.matches.get_mut(&match_id) {
+            Some(x) => {
+                let old = x.conn(player);
+                x.set_conn(player, id);
+                let since = x.away.iter().find(|&&(p, _)| p == player).map(|a| a.1);
+                x.away.retain(|&(p, _)| p != player);
+                // A clock stopped while its player was away starts again
+                // where it stopped. If they went away before their turn
+                // began, only the part of the turn they missed is theirs.
+                let paused = pause && x.game.clock.is_some() && x.game.player == player;
+                if let (true, Some(since)) = (paused, since) {
+                    x.turn_started += now.duration_since(since.max(x.turn_started));
+                }
+                (old, x.state(now), paused)
+            }
+            None => return,
+        };
+
+        // The old connection may not have noticed it is dead yet
+        if let Some(conn) = self.conns.get_mut(&old) {
+            conn.name = None;
+            conn.seat = None;
+            let _ = conn.stream.shutdown(Shutdown::Both);
+        }
+        if let Some(conn) = self.conns.get_mut(&id) {
+            conn.name = Some(String::from(game.name(player)));
+            conn.seat = Some((match_id, player));
+        }
+        println!(
+            "Game {}: {} is back on connection {}",
+            match_id,
+            game.name(player),
+            id
+        );
+        self.send(
+            id,
+            &Message::SeatAssigned {
+                player,
+                opponent: String::from(game.name(player.switch())),
+            },
+        );
+        self.send(id, &Message::Session(token));
+        self.send(id, &Message::State(Box::new(game.clone())));
+        let audience = match self.matches.get(&match_id) {
+            Some(x) => x.audience(),
+            None => return,
+        };
+        for other in audience.into_iter().filter(|&other| other != id) {
+            self.send(other, &Message::PlayerBack(player));
+            if paused {
+                self.send(other, &Message::State(Box::new(game.clone())));
+            }
+        }
+    }
+
+    /// Passes on something a player has said to everyone in their game
+    fn chat(&mut self, id: ConnId, text: &str) {
+        let now = Instant::now();
+        let (match_id, name, recent) = match self.conns.get_mut(&id) {
+            Some(conn) => {
+                while conn
+                    .chatted
+                    .front()
+                    .is_some_and(|&at| now.duration_since(at) >= CHAT_WINDOW)
+                {
+                    conn.chatted.pop_front();
+                }
+                match conn.seat {
+                    Some((match_id, _)) => (
+                        match_id,
+                        conn.name.clone().unwrap_or_default(),
+                        conn.chatted.len(),
+                    ),
+                    None => {
+                        let reason = String::from("Only players may chat.");
+                        return self.send(id, &Message::Refused(reason));
+                    }
+                }
+            }
+            None => return,
+        };
+        if recent >= CHAT_BURST {
+            let reason = String::from("You are chatting too quickly. Wait a moment.");
+            return self.send(id, &Message::Refused(reason));
+        }
+        let text = match protocol::chat_text(text) {
+            Ok(text) => text,
+            Err(reason) => return self.send(id, &Message::Refused(reason)),
+        };
+        if let Some(conn) = self.conns.get_mut(&id) {
+            conn.chatted.push_back(now);
+        }
+        self.broadcast(match_id, &Message::ChatMessage { from: name, text });
+    }
+
+    /// Sends a client the lists of open and running games
+    fn send_games(&mut self, id: ConnId) {
+        let open = self
+            .open
+            .iter()
+            .map(|(&game, open)| OpenGame {
+                id: game,
+                host: self.name(open.host),
+                color: open.color,
+            })
+            .collect();
+        let mut running: Vec<RunningGame> = self
+            .matches
+            .iter()
+            .map(|(&game, x)| RunningGame {
+                id: game,
+                black: x.game.black_name.clone(),
+                white: x.game.white_name.clone(),
+            })
+            .collect();
+        running.sort_by_key(|g| g.id);
+        self.send(id, &Message::Games { open, running });
+    }
+
+    /// Handles a request from a client which is looking for a game
+    fn lobby(&mut self, id: ConnId, m: Message) {
+        let (registered, seated, watching) = match self.conns.get(&id) {
+            Some(conn) => (
+                conn.name.is_some(),
+                conn.seat.is_some(),
+                conn.watching.is_some(),
+            ),
+            None => return,
+        };
+        let waiting = self.waiting == Some(id) || self.open.values().any(|o| o.host == id);
+        let refusal = match m {
+            _ if !registered => Some("Say hello before entering the lobby."),
+            Message::ListGames => None,
+            _ if seated => Some("You are already playing a game."),
+            _ if watching => Some("You are already watching a game."),
+            _ if waiting => Some("You are already waiting for an opponent."),
+            _ => None,
+        };
+        if let Some(reason) = refusal {
+            return self.send(id, &Message::Refused(String::from(reason)));
+        }
+
+        match m {
+            Message::ListGames => self.send_games(id),
+            Message::CreateGame(color) => {
+                let game = self.next_match;
+                self.next_match += 1;
+                self.open.insert(game, Open { host: id, color });
+                println!("Connection {} opened game {}", id, game);
+                self.send(id, &Message::GameCreated(game));
+            }
+            Message::JoinGame(game) => match self.open.get(&game) {
+                Some(open) if self.conns.contains_key(&open.host) => {
+                    let (host, color) = (open.host, open.color);
+                    self.open.remove(&game);
+                    match color {
+                        Some(Player::White) => self.start(game, id, host),
+                        _ => self.start(game, host, id),
+                    }
+                }
+                _ => {
+                    let reason = format!("There is no open game {}.", game);
+                    self.send(id, &Message::Refused(reason));
+                    self.send_games(id);
+                }
+            },
+            Message::QuickMatch => match self.waiting.take() {
+                Some(other) if self.conns.contains_key(&other) => {
+                    let game = self.next_match;
+                    self.next_match += 1;
+                    self.start(game, other, id);
+                }
+                _ => self.waiting = Some(id),
+            },
+            Message::Watch(game) => self.watch(id, game),
+            _ => (),
+        }
+    }
+
+    /// Adds a spectator to a running game, sending them its state
+    fn watch(&mut self, id: ConnId, match_id: MatchId) {
+        let watching = match self.matches.get_mut(&match_id) {
+            Some(x) => {
+                x.spectators.push(id);
+                Message::Watching {
+                    black: x.game.black_name.clone(),
+                    white: x.game.white_name.clone(),
+                }
+            }
+            None => {
+                let reason = format!("There is no game {} to watch.", match_id);
+                self.send(id, &Message::Refused(reason));
+                return self.send_games(id);
+            }
+        };
+        if let Some(conn) = self.conns.get_mut(&id) {
+            conn.watching = Some(match_id);
+        }
+        println!("Connection {} is watching game {}", id, match_id);
+        self.send(id, &watching);
+        if let Some(state) = self.matches.get(&match_id).map(|x| x.state(Instant::now())) {
+            self.send(id, &Message::State(Box::new(state)));
+        }
+    }
+
+    /// Seats two clients in a new game
+    fn start(&mut self, match_id: MatchId, black: ConnId, white: ConnId) {
+        let (black_name, white_name) = (self.name(black), self.name(white));
+        let mut game = Game::new();
+        game.black_name = black_name.clone();
+        game.white_name = white_name.clone();
+        game.clock = self.config.time.map(Clock::new);
+        self.matches.insert(
+            match_id,
+            Match {
+                black,
+                white,
+                game,
+                draw_offer: None,
+                spectators: Vec::new(),
+                away: Vec::new(),
+                turn_started: Instant::now(),
+            },
+        );
+        let mut tokens = Vec::new();
+        for &(id, player) in [(black, Player::Black), (white, Player::White)].iter() {
+            if let Some(conn) = self.conns.get_mut(&id) {
+                conn.seat = Some((match_id, player));
+            }
+            let token = self.new_token();
+            self.sessions.insert(token, (match_id, player));
+            tokens.push(token);
+        }
+        println!(
+            "Starting game {}: {} v. {}",
+            match_id, black_name, white_name
+        );
+        self.send(
+            black,
+            &Message::SeatAssigned {
+                player: Player::Black,
+                opponent: white_name,
+            },
+        );
+        self.send(black, &Message::Session(tokens[0]));
+        self.send(
+            white,
+            &Message::SeatAssigned {
+                player: Player::White,
+                opponent: black_name,
+            },
+        );
+        self.send(white, &Message::Session(tokens[1]));
+        self.broadcast_state(match_id);
+    }
+
+    /// Makes a session token which cannot be guessed
+    fn new_token(&self) -> Token {
+        loop {
+            // Each `RandomState` is seeded with different random keys
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(self.sessions.len());
+            let token = hasher.finish();
+            if !self.sessions.contains_key(&token) {
+                return token;
+            }
+        }
+    }
+
+    /// Sends a message to the players and spectators of a game
+    fn broadcast(&mut self, match_id: MatchId, m: &Message) {
+        let audience = match self.matches.get(&match_id) {
+            Some(x) => x.audience(),
+            None => return,
+        };
+        for id in audience {
+            self.send(id, m);
+        }
+    }
+
+    /// Sends the state of a game to its players and spectators
+    fn broadcast_state(&mut self, match_id: MatchId) {
+        let state = match self.matches.get(&match_id) {
+            Some(x) => Message::State(Box::new(x.state(Instant::now()))),
+            None => return,
+        };
+        self.broadcast(match_id, &state);
+    }
+
+    /// Handles a message from a seated player
+    fn play(&mut self, match_id: MatchId, player: Player, m: Message) {
+        let now = Instant::now();
+        let (me, opponent, turn, draw_offer, flagged) = match self.matches.get(&match_id) {
+            Some(x) => (
+                x.conn(player),
+                x.conn(player.switch()),
+                x.game.player,
+                x.draw_offer,
+                x.game.out_of_time(now.duration_since(x.turn_started)),
+            ),
+            None => return,
+        };
+
+        match m {
+            Message::MoveRequest(_) if turn != player => {
+                self.send(me, &Message::MoveRejected(Rejection::NotYourTurn));
+            }
+            Message::MoveRequest(_) if flagged => self.flag(match_id),
+            Message::MoveRequest(mv) => {
+                let played = match self.matches.get_mut(&match_id) {
+                    Some(x) => x.game.do_move(&mv).map(|_| {
+                        let thinking = now.duration_since(x.turn_started);
+                        if let Some(clock) = x.game.clock.as_mut() {
+                            clock.press(player, thinking);
+                        }
+                        x.turn_started = now;
+                        x.draw_offer = None;
+                        x.game.win_condition()
+                    }),
+                    None => return,
+                };
+                match played {
+                    Ok(winner) => {
+                        self.broadcast(match_id, &Message::MoveAccepted(mv));
+                        self.broadcast_state(match_id);
+                        if let Some(winner) = winner {
+                            let reason = format!("{} cannot move.", self.name(opponent));
+                            self.finish(match_id, Some(winner), reason);
+                        }
+                    }
+                    Err(e) => {
+                        println!(
+                            "Game {}: rejected {} from {:?}: {}",
+                            match_id, mv, player, e
+                        );
+                        self.send(me, &Message::MoveRejected(Rejection::Illegal(e)));
+                    }
+                }
+            }
+            Message::Resign => {
+                let reason = format!("{} resigned.", self.name(me));
+                self.finish(match_id, Some(player.switch()), reason);
+            }
+            Message::DrawOffer if draw_offer == Some(player.switch()) => {
+                self.finish(match_id, None, String::from("Draw agreed."));
+            }
+            Message::DrawOffer => {
+                if let Some(x) = self.matches.get_mut(&match_id) {
+                    x.draw_offer = Some(player);
+                }
+                self.send(opponent, &Message::DrawOffer);
+            }
+            _ => (),
+        }
+    }
+
+    /// Ends a game, telling its players and spectators the result
+    fn finish(&mut self, match_id: MatchId, winner: Option<Player>, reason: String) {
+        let x = match self.matches.remove(&match_id) {
+            Some(x) => x,
+            None => return,
+        };
+        println!("Game {} is over: {}", match_id, reason);
+        self.sessions.retain(|_, &mut (m, _)| m != match_id);
+        for id in x.audience() {
+            if let Some(conn) = self.conns.get_mut(&id) {
+                conn.seat = None;
+                conn.watching = None;
+            }
+            self.send(
+                id,
+                &Message::GameOver {
+                    winner,
+                    reason: reason.clone(),
+                },
             );
-            stream.shutdown(Shutdown::Both).unwrap();
-            false
         }
-    } {}
+    }
+
+    fn closed(&mut self, id: ConnId) {
+        let conn = match self.conns.remove(&id) {
+            Some(conn) => conn,
+            None => return,
+        };
+        println!("Connection {} closed", id);
+        if self.waiting == Some(id) {
+            self.waiting = None;
+        }
+        let hosted: Vec<MatchId> = self
+            .open
+            .iter()
+            .filter(|&(_, open)| open.host == id)
+            .map(|(&game, _)| game)
+            .collect();
+        for game in hosted {
+            self.open.remove(&game);
+        }
+        if let Some(x) = conn.watching.and_then(|m| self.matches.get_mut(&m)) {
+            x.spectators.retain(|&s| s != id);
+        }
+        if let Some((match_id, player)) = conn.seat {
+            self.away(match_id, player, conn.name.unwrap_or_default());
+        }
+    }
+
+    /// Gives a player whose connection has dropped the grace period to come
+    /// back in before they lose
+    fn away(&mut self, match_id: MatchId, player: Player, name: String) {
+        let grace = self.config.grace;
+        if grace == Duration::from_secs(0) {
+            let reason = format!("{} disconnected.", name);
+            return self.finish(match_id, Some(player.switch()), reason);
+        }
+        match self.matches.get_mut(&match_id) {
+            Some(x) => x.away.push((player, Instant::now())),
+            None => return,
+        }
+        println!("Game {}: {} is away", match_id, name);
+        let grace_ms = grace.as_secs() * 1000 + u64::from(grace.subsec_millis());
+        self.broadcast(match_id, &Message::PlayerAway { player, grace_ms });
+    }
+
+    fn tick(&mut self, now: Instant) {
+        self.heartbeat(now);
+        self.expire(now);
+        self.flags(now);
+    }
+
+    /// Ends the games of players who have run out of time. The clock of a
+    /// player who is away may be stopped until they come back.
+    fn flags(&mut self, now: Instant) {
+        let pause = self.config.away_clock == AwayClock::Paused;
+        let flagged: Vec<MatchId> = self
+            .matches
+            .iter()
+            .filter(|&(_, x)| {
+                let away = x.away.iter().any(|&(p, _)| p == x.game.player);
+                !(pause && away) && x.game.out_of_time(now.duration_since(x.turn_started))
+            })
+            .map(|(&match_id, _)| match_id)
+            .collect();
+        for match_id in flagged {
+            self.flag(match_id);
+        }
+    }
+
+    /// Ends a game which the player to move has run out of time in
+    fn flag(&mut self, match_id: MatchId) {
+        let (player, name) = match self.matches.get(&match_id) {
+            Some(x) => (x.game.player, String::from(x.game.name(x.game.player))),
+            None => return,
+        };
+        let reason = format!("{} ran out of time.", name);
+        self.finish(match_id, Some(player.switch()), reason);
+    }
+
+    /// Pings the connections which are due a heartbeat
+    fn heartbeat(&mut self, now: Instant) {
+        let heartbeat = self.config.heartbeat;
+        let due: Vec<ConnId> = self
+            .conns
+            .iter()
+            .filter(|&(_, conn)| now.duration_since(conn.pinged) >= heartbeat)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in due {
+            if let Some(conn) = self.conns.get_mut(&id) {
+                conn.pinged = now;
+            }
+            self.pings += 1;
+            let ping = Message::Ping(self.pings);
+            self.send(id, &ping);
+        }
+    }
+
+    /// Ends the games of players who have been away for too long. A game
+    /// both of whose players are away is abandoned without a winner.
+    fn expire(&mut self, now: Instant) {
+        let grace = self.config.grace;
+        let expired: Vec<(MatchId, Player)> = self
+            .matches
+            .iter()
+            .filter_map(|(&match_id, x)| {
+                x.away
+                    .iter()
+                    .find(|&&(_, since)| now.duration_since(since) >= grace)
+                    .map(|&(p, _)| (match_id, p))
+            })
+            .collect();
+        for (match_id, player) in expired {
+            let (name, abandoned) = match self.matches.get(&match_id) {
+                Some(x) => (
+                    String::from(x.game.name(player)),
+                    x.away.iter().any(|&(p, _)| p != player),
+                ),
+                None => continue,
+            };
+            if abandoned {
+                let reason = String::from("Neither player came back.");
+                self.finish(match_id, None, reason);
+            } else {
+                let reason = format!("{} did not come back.", name);
+                self.finish(match_id, Some(player.switch()), reason);
+            }
+        }
+    }
 }
 
-pub fn main() {
-    let listener = TcpListener::bind("0.0.0.0:3333").unwrap();
-    // accept connections and process them, spawning a new thread for each one
-    println!("Server listening on port 3333");
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                println!("New connection: {}", stream.peer_addr().unwrap());
-                thread::spawn(move || {
-                    // connection succeeded
-                    handle_client(stream)
-                });
+/// Reads messages from a connection until it closes
+fn read_conn(id: ConnId, mut stream: TcpStream, events: Sender<Event>) {
+    loop {
+        match protocol::recv(&mut stream) {
+            Ok(m) => {
+                if events.send(Event::Received(id, m)).is_err() {
+                    break;
+                }
             }
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                println!("Connection {} timed out", id);
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    let _ = events.send(Event::Closed(id));
+}
+
+/// Writes the messages queued for a connection until the server lets go of
+/// the queue or the client stops reading, then closes it
+fn write_conn(id: ConnId, mut stream: TcpStream, outbox: Receiver<Message>) {
+    for m in outbox.iter() {
+        if let Err(e) = protocol::send(&mut stream, &m) {
+            println!("Could not write to connection {}: {}", id, e);
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn run(events: Receiver<Event>, config: Config) {
+    let mut server = Server {
+        config,
+        ..Server::default()
+    };
+    loop {
+        match events.recv_timeout(TICK) {
+            Ok(e) => server.handle(e),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        server.tick(Instant::now());
+    }
+}
+
+/// Accepts connections on `listener` and hosts games between them until
+/// the listener fails
+pub fn serve(listener: TcpListener) -> io::Result<()> {
+    serve_with(listener, Config::default())
+}
+
+/// Like `serve`, treating players whose connections drop as `config` says
+pub fn serve_with(listener: TcpListener, config: Config) -> io::Result<()> {
+    let timeout = config.timeout;
+    let (tx, rx) = channel();
+    thread::spawn(move || run(rx, config));
+
+    // accept connections and process them, spawning a new thread for each one
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
             Err(e) => {
                 println!("Error: {}", e);
-                /* connection failed */
+                continue;
             }
+        };
+        // Turns are small and should be sent as soon as they are written
+        let _ = stream.set_nodelay(true);
+        // A client which stops reading holds up only its own writer
+        let timeouts = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)));
+        if let Err(e) = timeouts {
+            println!("Error: {}", e);
+            continue;
         }
+        match (stream.peer_addr(), stream.try_clone(), stream.try_clone()) {
+            (Ok(addr), Ok(reader), Ok(writer)) => {
+                println!("New connection {}: {}", id, addr);
+                let (outbox, queued) = sync_channel(OUTBOX_LEN);
+                let events = tx.clone();
+                if tx.send(Event::Connected(id, stream, outbox)).is_err() {
+                    break;
+                }
+                thread::spawn(move || write_conn(id, writer, queued));
+                thread::spawn(move || read_conn(id, reader, events));
+            }
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => println!("Error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Listens where `settings` say to and hosts games as `config` says until
+/// the server fails
+pub fn main(settings: &Settings, config: Config) -> Result<(), String> {
+    let (bind, port) = settings.bind_addr();
+    let listener = TcpListener::bind((bind, port))
+        .map_err(|e| format!("Could not listen on {}:{}: {}", bind, port, e))?;
+    println!("Server listening on {}:{}", bind, port);
+    if let Some(time) = config.time {
+        println!("Games are played with {} on the clock", time);
     }
-    // close the socket server
-    drop(listener);
+    serve_with(listener, config).map_err(|e| format!("The server failed: {}", e))
 }
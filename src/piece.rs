@@ -7,7 +7,10 @@
 use crate::player::Player;
 
 /// A `Piece` can either be a `Player` or a kinged `Player`
-#[derive(Copy, Clone, Debug, PartialEq)]
+///
+/// Serialized as the kind of piece keyed by its owner, e.g.
+/// `{"Normal":"Black"}` or `{"King":"White"}`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Piece {
     Normal(Player),
     King(Player),
@@ -63,4 +66,14 @@ mod tests {
         let piece = Piece::King(Player::Black);
         assert_eq!(piece.player(), Player::Black);
     }
+
+    #[test]
+    fn serialize_json() {
+        let json = serde_json::to_string(&Piece::white_king()).unwrap();
+        assert_eq!(json, r#"{"King":"White"}"#);
+        assert_eq!(
+            serde_json::from_str::<Piece>(&json).unwrap(),
+            Piece::white_king()
+        );
+    }
 }
@@ -1,6 +1,7 @@
 //! Management and matching of players
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// Serialized as the name of the player, e.g. `"Black"`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Player {
     Black,
     White,
@@ -0,0 +1,166 @@
+//! Saving and resuming games in progress
+//!
+//! A save file is the JSON serialization of a [`Game`](../game/struct.Game.html)
+//! wrapped with the version of the save format and the side the computer
+//! plays, if either, e.g.
+//! `{"version":1,"game":{"board":"-w-w-w-w...","player":"Black",...},"computer":"White"}`.
+
+use crate::game::Game;
+use crate::player::Player;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The version of the save file format. This is to be incremented whenever
+/// a change to the serialization of `Game` prevents older saves from loading.
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    game: Game,
+    /// Missing from games saved before the computer's side was kept
+    #[serde(default)]
+    computer: Option<Player>,
+}
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    game: &'a Game,
+    computer: Option<Player>,
+}
+
+/// The directory in which games are saved by default. This follows the XDG
+/// base directory specification, falling back to the working directory.
+pub fn data_dir() -> PathBuf {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir).join("rustedcheckers"),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local/share/rustedcheckers"),
+            None => PathBuf::from("."),
+        },
+    }
+}
+
+/// Where a game is saved when no file is specified
+pub fn default_path() -> PathBuf {
+    data_dir().join("saved.json")
+}
+
+/// Where a game is saved automatically after each turn and on exit
+pub fn autosave_path() -> PathBuf {
+    data_dir().join("autosave.json")
+}
+
+/// Saves `game`, with its board, turns played, players and clock, to `path`
+/// as versioned JSON, along with the side `computer` plays, if either
+pub fn save<P: AsRef<Path>>(game: &Game, computer: Option<Player>, path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    let json = serde_json::to_string_pretty(&SaveFileRef {
+        version: VERSION,
+        game,
+        computer,
+    })
+    .map_err(|e| format!("Could not serialize game: {}", e))?;
+    write(path, &json)
+}
+
+/// Writes `contents` to `path`, creating its directory if need be. The file
+/// is written alongside its destination and then moved into place, so an
+/// interrupted write never leaves a truncated file behind.
+pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        }
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| format!("Could not write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+/// Reads a game previously written by [`save()`](./fn.save.html), and the
+/// side the computer plays in it, if either
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(Game, Option<Player>), String> {
+    let path = path.as_ref();
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let file: SaveFile = serde_json::from_str(&json)
+        .map_err(|e| format!("{} is not a saved game: {}", path.display(), e))?;
+    match file.version {
+        VERSION => Ok((file.game, file.computer)),
+        v => Err(format!(
+            "{} was saved in format version {}, but only version {} is supported.",
+            path.display(),
+            v,
+            VERSION
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::moves::Submove;
+
+    /// A file in the temporary directory which no other test or test run
+    /// uses, for the test to remove once it is done
+    fn tmp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "rustedcheckers-test-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    #[test]
+    fn save_load() {
+        let path = tmp_path("save_load.json");
+        let mut g = Game::new();
+        g.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+        g.white_name = String::from("Derek");
+        g.clock = Some(Clock::new("5+3".parse().unwrap()));
+        save(&g, Some(Player::Black), &path).unwrap();
+        assert_eq!(load(&path), Ok((g, Some(Player::Black))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_missing() {
+        assert!(load(tmp_path("missing.json")).is_err());
+    }
+
+    #[test]
+    fn load_untimed() {
+        let path = tmp_path("load_untimed.json");
+        let json = serde_json::to_string(&SaveFileRef {
+            version: VERSION,
+            game: &Game::new(),
+            computer: None,
+        })
+        .unwrap();
+        // As written before games had clocks or kept the computer's side
+        let json = json.replace(",\"clock\":null", "");
+        fs::write(&path, json.replace(",\"computer\":null", "")).unwrap();
+        assert_eq!(load(&path), Ok((Game::new(), None)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_version() {
+        let path = tmp_path("load_version.json");
+        let json = serde_json::to_string(&SaveFileRef {
+            version: VERSION + 1,
+            game: &Game::new(),
+            computer: None,
+        })
+        .unwrap();
+        fs::write(&path, json).unwrap();
+        assert!(load(&path).unwrap_err().contains("format version 2"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,377 @@
+//! Drawing the board, and the move list, clock and captures beside it
+
+use crate::ui::layout::{win_size, Windows};
+use crate::ui::prefs::{Labels, LastMove, Orientation, Prefs};
+use crate::ui::theme::{
+    glyph, in_colour, COLOR_PAIR_BLACK, COLOR_PAIR_BLUE_CURSOR, COLOR_PAIR_BLUE_HI,
+    COLOR_PAIR_BLUE_LAST, COLOR_PAIR_BLUE_ON_BKGD, COLOR_PAIR_BLUE_ON_BLACK,
+    COLOR_PAIR_BLUE_ON_WHITE, COLOR_PAIR_CAPTURED, COLOR_PAIR_DEFAULT, COLOR_PAIR_EMPTY_CURSOR,
+    COLOR_PAIR_EMPTY_HI, COLOR_PAIR_EMPTY_LAST, COLOR_PAIR_LABEL, COLOR_PAIR_RED_CURSOR,
+    COLOR_PAIR_RED_HI, COLOR_PAIR_RED_LAST, COLOR_PAIR_RED_ON_BKGD, COLOR_PAIR_RED_ON_BLACK,
+    COLOR_PAIR_RED_ON_WHITE, COLOR_PAIR_WHITE,
+};
+
+use rustedcheckers::board::{Board, Position, BOARD_WIDTH};
+use rustedcheckers::clock;
+use rustedcheckers::game::Game;
+use rustedcheckers::moves::Move;
+use rustedcheckers::piece::Piece;
+use rustedcheckers::player::Player;
+
+use ncurses::*;
+
+use std::char;
+use std::time::{Duration, Instant};
+
+/// How long the squares of captured pieces stay marked, in milliseconds
+static CAPTURE_MARK_MS: u64 = 1500;
+
+pub fn draw_captured(win: WINDOW, board: &Board) {
+    // Get number of captured pieces
+    let w_cap = 12 - board.count_pieces(Player::White);
+    let b_cap = 12 - board.count_pieces(Player::Black);
+
+    // Title, over whatever was drawn before
+    werase(win);
+    box_(win, 0, 0);
+    mvwaddstr(win, 0, 1, "┤Captured├");
+
+    // Draw captured red pieces
+    wcolor_set(win, COLOR_PAIR_RED_ON_BKGD);
+    mvwaddstr(win, 1, 1, &w_cap.to_string());
+    if w_cap > 0 {
+        for x in 2..(w_cap + 2) {
+            mvwaddstr(win, 1, x as i32 * 2, glyph(Piece::Normal(Player::White)));
+        }
+    }
+
+    // Draw captured blue pieces
+    wcolor_set(win, COLOR_PAIR_BLUE_ON_BKGD);
+    mvwaddstr(win, 2, 1, &b_cap.to_string());
+    if b_cap > 0 {
+        for y in 2..(b_cap + 2) {
+            mvwaddstr(win, 2, y as i32 * 2, glyph(Piece::Normal(Player::Black)));
+        }
+    }
+
+    wrefresh(win);
+}
+
+/// Draws the time each player has left, with the player to move having been
+/// thinking for `thinking` since the clock was read
+pub fn draw_clock(win: WINDOW, game: &Game, thinking: Duration) {
+    mvwaddstr(win, 0, 1, "┤Clock├");
+    let rows = [
+        (1, Player::White, COLOR_PAIR_RED_ON_BKGD),
+        (2, Player::Black, COLOR_PAIR_BLUE_ON_BKGD),
+    ];
+    for &(row, p, pair) in rows.iter() {
+        wcolor_set(win, pair);
+        mvwaddstr(win, row, 1, glyph(Piece::Normal(p)));
+        wcolor_set(win, COLOR_PAIR_DEFAULT);
+        let left = match game.time_left(p, thinking) {
+            Some(left) => clock::format_time(left),
+            None => String::from("--:--"),
+        };
+        // The clock which is running is shown reversed
+        if p == game.player && game.clock.is_some() {
+            wattron(win, A_REVERSE());
+        }
+        mvwaddstr(win, row, 3, &format!("{:>9}", left));
+        wattroff(win, A_REVERSE());
+    }
+    wrefresh(win);
+}
+
+/// How the board is shown, apart from the position on it
+#[derive(Debug, Clone, Copy)]
+pub struct Look {
+    pub orientation: Orientation,
+    pub labels: Labels,
+    last_move: LastMove,
+}
+
+impl Look {
+    /// The look the player prefers
+    pub fn new(prefs: &Prefs) -> Look {
+        Look {
+            orientation: prefs.orientation,
+            labels: prefs.labels,
+            last_move: prefs.last_move,
+        }
+    }
+}
+
+/// Draws the board as `look` says with each square `cell` rows by columns,
+/// and the keyboard cursor on the square at `cursor`. The squares of the
+/// pieces captured in the last turn are marked if `captures`.
+fn draw_board(
+    win: WINDOW,
+    game: &mut Game,
+    cursor: Position,
+    cell: (i32, i32),
+    look: Look,
+    captures: bool,
+) {
+    // Border and title, over any labels drawn before
+    box_(win, 0, 0);
+    mvwaddstr(win, 0, 1, "┤Board├");
+
+    // The last turn played, shaded where there is colour and dotted where
+    // there is not
+    let colour = in_colour();
+    let side = look.orientation.side(game.player);
+    let last = match look.last_move {
+        LastMove::Off => None,
+        _ => game.history.last(),
+    };
+    let shade = colour && look.last_move == LastMove::Shade;
+
+    // Board
+    for (i, x) in game.board.board.iter().enumerate() {
+        for (j, y) in x.iter().enumerate() {
+            let here = Position::new(i, j);
+            let dark = (i + j) % 2 == 1;
+            let hilighted = game.hilighted.contains(&here);
+            let white = y.map(|p| p.player() == Player::White);
+            // Whether the last turn passed through, ended on, or captured on
+            // the square
+            let (trail, landed, taken) = match last {
+                Some(m) => (
+                    m.from == here || m.path.contains(&here),
+                    m.to() == here,
+                    captures && m.captured.iter().any(|&(p, _)| p == here),
+                ),
+                None => (false, false, false),
+            };
+            let plain = here != cursor && !hilighted && game.selected != Some(here);
+            if colour {
+                let pair = if here == cursor {
+                    match white {
+                        Some(true) => COLOR_PAIR_RED_CURSOR,
+                        Some(false) => COLOR_PAIR_BLUE_CURSOR,
+                        None => COLOR_PAIR_EMPTY_CURSOR,
+                    }
+                } else if hilighted {
+                    COLOR_PAIR_EMPTY_HI
+                } else if game.selected == Some(here) {
+                    match white {
+                        Some(true) => COLOR_PAIR_RED_HI,
+                        _ => COLOR_PAIR_BLUE_HI,
+                    }
+                } else if shade && trail {
+                    match white {
+                        Some(true) => COLOR_PAIR_RED_LAST,
+                        Some(false) => COLOR_PAIR_BLUE_LAST,
+                        None => COLOR_PAIR_EMPTY_LAST,
+                    }
+                } else {
+                    match (white, dark) {
+                        (Some(true), true) => COLOR_PAIR_RED_ON_BLACK,
+                        (Some(true), false) => COLOR_PAIR_RED_ON_WHITE,
+                        (Some(false), true) => COLOR_PAIR_BLUE_ON_BLACK,
+                        (Some(false), false) => COLOR_PAIR_BLUE_ON_WHITE,
+                        (None, true) => COLOR_PAIR_BLACK,
+                        (None, false) => COLOR_PAIR_WHITE,
+                    }
+                };
+                wcolor_set(win, pair);
+            } else {
+                // Light squares are shown reversed, and the cursor reverses
+                // whichever square it is on
+                let mut attrs = match dark {
+                    true => A_NORMAL(),
+                    _ => A_REVERSE(),
+                };
+                if here == cursor {
+                    attrs ^= A_REVERSE();
+                }
+                if game.selected == Some(here) {
+                    attrs |= A_BOLD() | A_UNDERLINE();
+                }
+                wattrset(win, attrs);
+            }
+            // Fill the square, then put the piece, or a mark, in the middle
+            // of it
+            let seen = here.norm(side);
+            let (top, left) = (seen.0 as i32 * cell.0 + 1, seen.1 as i32 * cell.1 + 1);
+            for row in 0..cell.0 {
+                mvwaddstr(win, top + row, left, &" ".repeat(cell.1 as usize));
+            }
+            let (mid, centre) = (top + (cell.0 - 1) / 2, left + (cell.1 - 2) / 2);
+            let mark = match y {
+                Some(_) => None,
+                // Without colour, the squares the piece can move to are
+                // marked instead
+                None if hilighted && !colour => Some("<>"),
+                None if taken && plain => Some("xx"),
+                None if trail && plain && !shade => Some(".."),
+                None => None,
+            };
+            if let Some(p) = y {
+                // Unless shaded, the piece which moved last is underlined
+                if landed && !shade {
+                    wattron(win, A_UNDERLINE());
+                }
+                mvwaddstr(win, mid, centre, glyph(*p));
+                wattroff(win, A_UNDERLINE());
+            } else if let Some(mark) = mark {
+                if taken && colour {
+                    wcolor_set(win, COLOR_PAIR_CAPTURED);
+                }
+                mvwaddstr(win, mid, centre, mark);
+            }
+            // Square numbers go below the piece, or in place of it if there
+            // is no room, on squares not otherwise marked
+            let plain = plain && !(shade && trail);
+            if let (Labels::Squares, Some(n), true) = (look.labels, here.square(), plain) {
+                if colour {
+                    wcolor_set(win, COLOR_PAIR_LABEL);
+                }
+                let number = format!("{:>2}", n);
+                if cell.0 > 1 {
+                    mvwaddstr(win, top + cell.0 - 1, left + cell.1 - 2, &number);
+                } else if y.is_none() && mark.is_none() {
+                    mvwaddstr(win, mid, centre, &number);
+                }
+            }
+        }
+    }
+    wattrset(win, A_NORMAL());
+    wcolor_set(win, COLOR_PAIR_DEFAULT);
+
+    // Rows and columns are labelled on the border, numbered and lettered
+    // from Black's side
+    if look.labels == Labels::RowsAndColumns {
+        let n = BOARD_WIDTH;
+        for k in 0..n {
+            // The row and column drawn k squares from the top and left
+            let Position(row, col) = Position::new(k, k).norm(side);
+            let y = 1 + k as i32 * cell.0 + (cell.0 - 1) / 2;
+            mvwaddstr(win, y, 0, &(n - row).to_string());
+            let x = 1 + k as i32 * cell.1 + (cell.1 - 1) / 2;
+            let file = char::from(b'a' + col as u8);
+            mvwaddstr(win, 1 + n as i32 * cell.0, x, &file.to_string());
+        }
+    }
+
+    // Refresh
+    wrefresh(win);
+}
+
+/// A turn as shown in the move list: in standard notation, with a `K` if the
+/// piece was crowned
+fn move_text(m: &Move) -> String {
+    match m.promoted {
+        true => format!("{}K", m),
+        _ => m.to_string(),
+    }
+}
+
+/// Draws the turns played so far, Black's and White's side by side, with the
+/// last of the first `shown` highlighted. The list scrolls to keep it in view.
+pub fn draw_moves(win: WINDOW, game: &Game, shown: usize) {
+    let (h, w) = win_size(win);
+    werase(win);
+    box_(win, 0, 0);
+    mvwaddstr(win, 0, 1, "┤Moves├");
+    mvwaddstr(win, h - 2, 1, "[ ] to step, End: now");
+
+    // A game set up with White to move starts its list in White's column
+    let first = match game.history.len() % 2 {
+        0 => game.player,
+        _ => game.player.switch(),
+    };
+    let offset = match first {
+        Player::Black => 0,
+        Player::White => 1,
+    };
+    let rows = (h - 3) as usize;
+    let current = (shown + offset).saturating_sub(1) / 2;
+    let top = (current + 1).saturating_sub(rows);
+    let width = ((w - 7) / 2) as usize;
+
+    for (i, m) in game.history.iter().enumerate() {
+        let row = (i + offset) / 2;
+        if row < top || row >= top + rows {
+            continue;
+        }
+        let y = (row - top) as i32 + 1;
+        let x = 6 + ((i + offset) % 2 * (width + 1)) as i32;
+        mvwaddstr(win, y, 1, &format!("{:>3}.", row + 1));
+        let text: String = move_text(m).chars().take(width).collect();
+        if i + 1 == shown {
+            wattron(win, A_REVERSE());
+        }
+        mvwaddstr(win, y, x, &text);
+        wattroff(win, A_REVERSE());
+    }
+    wrefresh(win);
+}
+
+/// Draws the board as it stood after the first `view` turns, or as it stands
+/// if `view` is `None`, as `look` says. The squares of the pieces just
+/// captured are marked if `captures`, but only on the board as it stands.
+pub fn draw_position(
+    wins: &Windows,
+    game: &mut Game,
+    view: Option<usize>,
+    cursor: Position,
+    look: Look,
+    captures: bool,
+) {
+    let cell = wins.layout.cell;
+    match view {
+        Some(n) => draw_board(
+            wins.board,
+            &mut game.position_after(n),
+            cursor,
+            cell,
+            look,
+            false,
+        ),
+        None => draw_board(wins.board, game, cursor, cell, look, captures),
+    }
+}
+
+/// Marks the squares of the pieces captured in each turn for a moment after
+/// it is played
+pub struct CaptureMarks {
+    /// How many turns had been played when last checked
+    turns: usize,
+    /// When the last turn was played, while its captures are marked
+    since: Option<Instant>,
+}
+
+impl CaptureMarks {
+    pub fn new(game: &Game) -> CaptureMarks {
+        CaptureMarks {
+            turns: game.history.len(),
+            since: None,
+        }
+    }
+
+    /// Notices a turn played, or the marks running out, since last checked.
+    /// Returns whether the board needs drawing again.
+    pub fn update(&mut self, game: &Game) -> bool {
+        if game.history.len() != self.turns {
+            self.turns = game.history.len();
+            self.since = match game.history.last() {
+                Some(m) if m.is_capture() => Some(Instant::now()),
+                _ => None,
+            };
+            return true;
+        }
+        match self.since {
+            Some(t) if t.elapsed() >= Duration::from_millis(CAPTURE_MARK_MS) => {
+                self.since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn showing(&self) -> bool {
+        self.since.is_some()
+    }
+}
@@ -0,0 +1,149 @@
+//! The chat pane shown beside networked games
+
+use crate::ui::layout::{win_size, Rect};
+use crate::ui::status;
+
+use rustedcheckers::networking::protocol::MAX_CHAT_LEN;
+
+use ncurses::*;
+
+use std::char;
+
+/// How many chat messages are kept to scroll back through
+pub static CHAT_HISTORY: usize = 100;
+
+/// Splits text into lines at most `width` characters long
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .chunks(width)
+        .map(|line| line.iter().collect())
+        .collect()
+}
+
+/// The chat window of a networked game, and the message being typed in it
+pub struct ChatPane {
+    pub win: WINDOW,
+    /// The message being typed, if one is
+    draft: Option<String>,
+    /// How many lines back through the history the window is scrolled
+    scroll: usize,
+}
+
+impl ChatPane {
+    pub fn new(rect: Rect) -> ChatPane {
+        ChatPane {
+            win: rect.create(),
+            draft: None,
+            scroll: 0,
+        }
+    }
+
+    /// Moves the window to `rect`
+    pub fn place(&mut self, rect: Rect) {
+        delwin(self.win);
+        self.win = rect.create();
+    }
+
+    /// How many lines of messages the window shows
+    pub fn rows(&self) -> i32 {
+        win_size(self.win).0 - 3
+    }
+
+    pub fn typing(&self) -> bool {
+        self.draft.is_some()
+    }
+
+    /// Starts typing a message
+    pub fn start(&mut self) {
+        self.draft = Some(String::new());
+        self.scroll = 0;
+        curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+        status("Type a message, then Enter to send it or Esc to stop.");
+    }
+
+    fn stop(&mut self) {
+        self.draft = None;
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        status("");
+    }
+
+    /// Handles a key pressed while typing, returning the message once it has
+    /// been finished
+    pub fn edit(&mut self, ch: Option<WchResult>) -> Option<String> {
+        match ch {
+            Some(WchResult::Char(10))
+            | Some(WchResult::Char(13))
+            | Some(WchResult::KeyCode(KEY_ENTER)) => {
+                let text = self.draft.take().unwrap_or_default();
+                self.stop();
+                if text.trim().is_empty() {
+                    None
+                } else {
+                    Some(text)
+                }
+            }
+            Some(WchResult::Char(27)) => {
+                self.stop();
+                None
+            }
+            Some(WchResult::Char(8))
+            | Some(WchResult::Char(127))
+            | Some(WchResult::KeyCode(KEY_BACKSPACE)) => {
+                if let Some(draft) = self.draft.as_mut() {
+                    draft.pop();
+                }
+                None
+            }
+            Some(WchResult::Char(c)) => {
+                let c = char::from_u32(c).filter(|c| !c.is_control());
+                if let (Some(draft), Some(c)) = (self.draft.as_mut(), c) {
+                    if draft.chars().count() < MAX_CHAT_LEN {
+                        draft.push(c);
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Scrolls back through the history by `lines`, or forward if negative
+    pub fn scroll_by(&mut self, lines: i32) {
+        self.scroll = if lines < 0 {
+            self.scroll.saturating_sub(-lines as usize)
+        } else {
+            self.scroll + lines as usize
+        };
+    }
+
+    /// Draws the messages in `history` which fit, latest at the bottom, above
+    /// the message being typed
+    pub fn draw(&mut self, history: &[String]) {
+        let (h, w) = win_size(self.win);
+        let width = (w - 2) as usize;
+        let rows = self.rows() as usize;
+        let lines: Vec<String> = history.iter().flat_map(|m| wrap(m, width)).collect();
+        self.scroll = self.scroll.min(lines.len().saturating_sub(rows));
+        let end = lines.len() - self.scroll;
+        let start = end.saturating_sub(rows);
+
+        werase(self.win);
+        box_(self.win, 0, 0);
+        mvwaddstr(self.win, 0, 1, "┤Chat├");
+        for (i, line) in lines[start..end].iter().enumerate() {
+            mvwaddstr(self.win, i as i32 + 1, 1, line);
+        }
+        let input = match &self.draft {
+            Some(draft) => {
+                // Show the end of a message too long to fit
+                let typed: Vec<char> = format!("> {}", draft).chars().collect();
+                let shown = typed.len().saturating_sub(width - 1);
+                typed[shown..].iter().collect()
+            }
+            None => String::from("t to talk, PgUp/PgDn to scroll"),
+        };
+        mvwaddstr(self.win, h - 2, 1, &input);
+        wrefresh(self.win);
+    }
+}
@@ -0,0 +1,220 @@
+//! Fitting the game's windows to the terminal
+
+use crate::ui::draw_title;
+use crate::ui::theme::COLOR_PAIR_DEFAULT;
+
+use rustedcheckers::board::BOARD_WIDTH;
+
+use ncurses::*;
+
+// Window Positioning
+pub static TITLE_POS: (i32, i32) = (0, 0);
+pub static TITLE_H: i32 = 3;
+static CAPTURE_H: i32 = 4;
+static CAPTURE_W: i32 = 2 + 2 + 1 + 12 * 2;
+static CLOCK_W: i32 = 2 + 2 + 8 + 1;
+static MOVES_W: i32 = 2 + 4 + 2 * 9;
+static CHAT_W: i32 = 40;
+/// Lines kept free below the windows for the status and connection lines
+static STATUS_H: i32 = 2;
+/// The sizes each square of the board may be drawn at, in rows and columns,
+/// largest first
+static CELLS: [(i32, i32); 2] = [(2, 4), (1, 2)];
+
+/// The size of a window and the position of its top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    h: i32,
+    w: i32,
+    y: i32,
+    x: i32,
+}
+
+impl Rect {
+    fn bottom(&self) -> i32 {
+        self.y + self.h
+    }
+
+    fn right(&self) -> i32 {
+        self.x + self.w
+    }
+
+    /// Creates a bordered window filling the rectangle
+    pub fn create(&self) -> WINDOW {
+        create_win(self.h, self.w, self.y, self.x)
+    }
+}
+
+/// Where each window goes, worked out from the size of the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    /// The size each square of the board is drawn at, in rows and columns
+    pub cell: (i32, i32),
+    board: Rect,
+    moves: Rect,
+    captured: Rect,
+    clock: Rect,
+    /// Where the chat window goes, in games which have one
+    pub chat: Rect,
+}
+
+impl Layout {
+    fn with_cell(cell: (i32, i32)) -> Layout {
+        let n = BOARD_WIDTH as i32;
+        let board = Rect {
+            h: n * cell.0 + 2,
+            w: n * cell.1 + 2,
+            y: TITLE_POS.0 + TITLE_H,
+            x: 0,
+        };
+        let moves = Rect {
+            h: board.h,
+            w: MOVES_W,
+            y: board.y,
+            x: board.right(),
+        };
+        let captured = Rect {
+            h: CAPTURE_H,
+            w: CAPTURE_W,
+            y: board.bottom(),
+            x: 0,
+        };
+        let clock = Rect {
+            h: CAPTURE_H,
+            w: CLOCK_W,
+            y: captured.y,
+            x: captured.right(),
+        };
+        let chat = Rect {
+            h: captured.bottom() - board.y,
+            w: CHAT_W,
+            y: board.y,
+            x: moves.right().max(clock.right()) + 1,
+        };
+        Layout {
+            cell,
+            board,
+            moves,
+            captured,
+            clock,
+            chat,
+        }
+    }
+
+    /// The lines and columns the layout needs, including the chat window if
+    /// `chat`
+    fn size(&self, chat: bool) -> (i32, i32) {
+        let mut rects = vec![self.moves, self.captured, self.clock];
+        if chat {
+            rects.push(self.chat);
+        }
+        let lines = rects.iter().map(Rect::bottom).max().unwrap_or(0) + STATUS_H;
+        let cols = rects.iter().map(Rect::right).max().unwrap_or(0);
+        (lines, cols)
+    }
+
+    /// The layout with the largest squares which fits in `lines` by `cols`,
+    /// or `None` if the terminal is too small for any
+    fn fit(lines: i32, cols: i32, chat: bool) -> Option<Layout> {
+        CELLS.iter().map(|&cell| Layout::with_cell(cell)).find(|l| {
+            let (h, w) = l.size(chat);
+            h <= lines && w <= cols
+        })
+    }
+}
+
+/// The windows a game is played in
+pub struct Windows {
+    pub layout: Layout,
+    pub board: WINDOW,
+    pub moves: WINDOW,
+    pub captured: WINDOW,
+    pub clock: WINDOW,
+}
+
+impl Windows {
+    /// Draws the title, and creates the windows where `layout` puts them
+    pub fn new(layout: Layout) -> Windows {
+        draw_title();
+        Windows {
+            layout,
+            board: layout.board.create(),
+            moves: layout.moves.create(),
+            captured: layout.captured.create(),
+            clock: layout.clock.create(),
+        }
+    }
+
+    pub fn close(&self) {
+        for &win in [self.clock, self.captured, self.moves, self.board].iter() {
+            delwin(win);
+        }
+    }
+
+    /// Redraws every window as it was, after something has been drawn over
+    /// them
+    pub fn uncover(&self) {
+        for &win in [self.board, self.moves, self.captured, self.clock].iter() {
+            touchwin(win);
+            wrefresh(win);
+        }
+    }
+}
+
+/// Waits until the terminal is large enough for a game, with room for the
+/// chat window if `chat`, and returns the layout which fits it. Returns
+/// `None` if the player quits while waiting.
+pub fn wait_for_room(chat: bool) -> Option<Layout> {
+    loop {
+        if let Some(layout) = Layout::fit(LINES(), COLS(), chat) {
+            clear();
+            refresh();
+            return Some(layout);
+        }
+        let smallest = CELLS[CELLS.len() - 1];
+        let (lines, cols) = Layout::with_cell(smallest).size(chat);
+        clear();
+        mvaddstr(0, 0, "The terminal is too small.");
+        mvaddstr(
+            1,
+            0,
+            &format!(
+                "Make it at least {} by {}, or press q to quit.",
+                cols, lines
+            ),
+        );
+        refresh();
+        loop {
+            match wget_wch(stdscr()) {
+                Some(WchResult::KeyCode(KEY_RESIZE)) => break,
+                Some(WchResult::Char(c)) if c == 'q' as u32 => return None,
+                _ => (),
+            }
+        }
+    }
+}
+
+/// The height and width of a window
+pub fn win_size(win: WINDOW) -> (i32, i32) {
+    let mut h = 0;
+    let mut w = 0;
+    getmaxyx(win, &mut h, &mut w);
+    (h, w)
+}
+
+/// Creates, refreshes, and returns a bordered window
+pub fn create_win(height: i32, width: i32, start_y: i32, start_x: i32) -> WINDOW {
+    let win = newwin(height, width, start_y, start_x);
+    wbkgd(win, COLOR_PAIR(COLOR_PAIR_DEFAULT));
+    box_(win, 0, 0);
+    wrefresh(win);
+    win
+}
+
+/// Destroys and refreshes a specified window
+pub fn destroy_win(win: WINDOW) {
+    let ch = ' ' as chtype;
+    wborder(win, ch, ch, ch, ch, ch, ch, ch, ch);
+    wrefresh(win);
+    delwin(win);
+}
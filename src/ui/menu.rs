@@ -0,0 +1,223 @@
+//! The start menu and the settings screen
+
+use crate::ui::layout::{create_win, destroy_win, TITLE_H, TITLE_POS};
+use crate::ui::play::{new_game, play};
+use crate::ui::prefs::{self, Prefs};
+use crate::ui::theme::{set_glyphs, set_theme};
+use crate::ui::{draw_title, prompt, status, Mode};
+
+use rustedcheckers::ai;
+use rustedcheckers::game::Game;
+use rustedcheckers::networking::Settings;
+use rustedcheckers::player::Player;
+use rustedcheckers::save;
+
+use ncurses::*;
+
+use std::char;
+use std::path::PathBuf;
+
+/// Shows `items` in a box headed `title`, and waits for one to be chosen
+/// with the arrow keys and Enter, or by typing its number. Returns `None` if
+/// Esc or q is pressed instead.
+fn choose(title: &str, items: &[String], selected: &mut usize) -> Option<usize> {
+    let longest = items.iter().map(|i| i.chars().count()).max().unwrap_or(0);
+    let digits = items.len().to_string().len();
+    let width = longest.max(title.chars().count() + 2) + 6 + digits;
+    let win = create_win(
+        items.len() as i32 + 2,
+        width as i32,
+        TITLE_POS.0 + TITLE_H + 1,
+        2,
+    );
+    let up = |i: usize| (i + items.len() - 1) % items.len();
+    let down = |i: usize| (i + 1) % items.len();
+    // The digits typed so far of a number which may run to another digit
+    let mut typed = 0;
+    let chosen = loop {
+        mvwaddstr(win, 0, 1, &format!("┤{}├", title));
+        for (i, item) in items.iter().enumerate() {
+            if i == *selected {
+                wattron(win, A_REVERSE());
+            }
+            let line = format!(
+                " {:>digits$} {:pad$} ",
+                i + 1,
+                item,
+                digits = digits,
+                pad = width - 5 - digits
+            );
+            mvwaddstr(win, i as i32 + 1, 1, &line);
+            wattroff(win, A_REVERSE());
+        }
+        wrefresh(win);
+        let c = match wget_wch(stdscr()) {
+            Some(WchResult::KeyCode(KEY_RESIZE)) => {
+                clear();
+                draw_title();
+                refresh();
+                touchwin(win);
+                ' '
+            }
+            Some(WchResult::KeyCode(KEY_UP)) => 'k',
+            Some(WchResult::KeyCode(KEY_DOWN)) => 'j',
+            Some(WchResult::KeyCode(KEY_ENTER)) => '\n',
+            Some(WchResult::Char(c)) => char::from_u32(c).unwrap_or(' '),
+            _ => ' ',
+        };
+        let before = typed;
+        typed = 0;
+        match c {
+            'k' => *selected = up(*selected),
+            'j' => *selected = down(*selected),
+            '\n' | '\r' => break Some(*selected),
+            'q' | '\u{1b}' => break None,
+            c => match c.to_digit(10).map(|d| before * 10 + d as usize) {
+                Some(n) if n >= 1 && n <= items.len() => {
+                    *selected = n - 1;
+                    // Wait to see whether a second digit follows
+                    match n * 10 > items.len() {
+                        true => break Some(*selected),
+                        false => typed = n,
+                    }
+                }
+                _ => (),
+            },
+        }
+    };
+    werase(win);
+    destroy_win(win);
+    chosen
+}
+
+/// Shows the start menu until the player quits. Network games are played
+/// where `settings` say.
+pub fn menu(settings: &Settings, mut prefs: Prefs) {
+    let items: Vec<String> = [
+        "Local game",
+        "Play the computer",
+        "Host a network game",
+        "Join a network game",
+        "Play on a server",
+        "Resume last game",
+        "Load a game",
+        "Settings",
+        "Quit",
+    ]
+    .iter()
+    .map(|&i| String::from(i))
+    .collect();
+    let mut selected = 0;
+    let mut message = String::new();
+    loop {
+        clear();
+        draw_title();
+        status(&message);
+        refresh();
+        let started = match choose("Menu", &items, &mut selected) {
+            Some(0) => {
+                let game = new_game(&prefs, prefs.time, None);
+                play(game, &save::default_path(), Mode::Local, &prefs)
+            }
+            Some(1) => {
+                let computer = prefs.color.unwrap_or(Player::Black).switch();
+                let game = new_game(&prefs, prefs.time, Some(computer));
+                play(
+                    game,
+                    &save::default_path(),
+                    Mode::Computer(computer),
+                    &prefs,
+                )
+            }
+            Some(2) => {
+                let mode = Mode::Host(settings.clone(), prefs.color, prefs.time);
+                play(Game::new(), &save::default_path(), mode, &prefs)
+            }
+            Some(3) => {
+                let addr = prompt("Address of the game to join", &settings.host);
+                let mode = Mode::Join(addr, settings.clone(), prefs.color);
+                play(Game::new(), &save::default_path(), mode, &prefs)
+            }
+            Some(4) => {
+                let mode = Mode::Connect(settings.clone());
+                play(Game::new(), &save::default_path(), mode, &prefs)
+            }
+            Some(5) => match save::autosave_path() {
+                path if !path.exists() => Err(String::from("There is no game to resume.")),
+                path => save::load(&path).and_then(|(game, computer)| {
+                    play(game, &save::default_path(), Mode::local(computer), &prefs)
+                }),
+            },
+            Some(6) => {
+                let default = save::default_path();
+                let path = PathBuf::from(prompt("Game to load", &default.to_string_lossy()));
+                save::load(&path)
+                    .and_then(|(game, computer)| play(game, &path, Mode::local(computer), &prefs))
+            }
+            Some(7) => edit_prefs(&mut prefs),
+            _ => return,
+        };
+        message = started.err().unwrap_or_default();
+    }
+}
+
+/// Lets the player change their preferences, and saves them once they are
+/// done
+fn edit_prefs(prefs: &mut Prefs) -> Result<(), String> {
+    let mut selected = 0;
+    loop {
+        let items = vec![
+            format!("Name: {}", prefs.name),
+            match prefs.color {
+                Some(p) => format!("Colour: {:?}", p),
+                None => String::from("Colour: either"),
+            },
+            match prefs.time {
+                Some(t) => format!("Clock: {}", t),
+                None => String::from("Clock: none"),
+            },
+            format!("Computer level: {} of {}", prefs.level, ai::LEVELS.len()),
+            format!("Theme: {}", prefs.theme.name()),
+            format!("Board seen from: {}", prefs.orientation.name()),
+            format!("Labels: {}", prefs.labels.name()),
+            format!("Pieces: {}", prefs.glyphs.name()),
+            format!("Last turn: {}", prefs.last_move.name()),
+            String::from("Done"),
+        ];
+        match choose("Settings", &items, &mut selected) {
+            Some(0) => prefs.name = prompt("Name", &prefs.name),
+            Some(1) => {
+                prefs.color = match prefs.color {
+                    None => Some(Player::Black),
+                    Some(Player::Black) => Some(Player::White),
+                    Some(Player::White) => None,
+                }
+            }
+            Some(2) => {
+                let current = prefs.time.map_or(String::from("none"), |t| t.to_string());
+                let time = prompt("Time control, e.g. 5, 5+3, 5d3 or 40/90, or none", &current);
+                match time.as_str() {
+                    "none" => prefs.time = None,
+                    t => match t.parse() {
+                        Ok(t) => prefs.time = Some(t),
+                        Err(e) => status(&e),
+                    },
+                }
+            }
+            Some(3) => prefs.level = prefs.level % ai::LEVELS.len() as u32 + 1,
+            Some(4) => {
+                prefs.theme = prefs.theme.next();
+                set_theme(prefs.theme);
+            }
+            Some(5) => prefs.orientation = prefs.orientation.next(),
+            Some(6) => prefs.labels = prefs.labels.next(),
+            Some(7) => {
+                prefs.glyphs = prefs.glyphs.next();
+                set_glyphs(prefs.glyphs);
+            }
+            Some(8) => prefs.last_move = prefs.last_move.next(),
+            _ => break,
+        }
+    }
+    prefs.save(prefs::default_path())
+}
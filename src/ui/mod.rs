@@ -0,0 +1,96 @@
+//! The screens of the ncurses front-end, and what they share
+
+mod board;
+mod chat;
+mod layout;
+pub mod menu;
+mod online;
+pub mod play;
+pub mod prefs;
+pub mod theme;
+
+use rustedcheckers::clock::TimeControl;
+use rustedcheckers::networking::Settings;
+use rustedcheckers::player::Player;
+
+use libc::{c_int, c_short};
+use ncurses::*;
+
+/// Who the game is played against
+pub enum Mode {
+    /// Both players share this terminal
+    Local,
+    /// The computer, playing the given colour
+    Computer(Player),
+    /// Someone elsewhere, through a server the player is asked for
+    Connect(Settings),
+    /// Someone who joins this game directly, which may be timed
+    Host(Settings, Option<Player>, Option<TimeControl>),
+    /// Someone hosting a game at the given address
+    Join(String, Settings, Option<Player>),
+}
+
+impl Mode {
+    /// A game played at this terminal, against the computer if it plays
+    /// `computer`
+    pub fn local(computer: Option<Player>) -> Mode {
+        computer.map_or(Mode::Local, Mode::Computer)
+    }
+}
+
+/// How long to wait for a key, in milliseconds, before checking the network
+static POLL_MS: i32 = 100;
+
+const fn new_mevent() -> MEVENT {
+    MEVENT {
+        id: 0 as c_short,
+        x: 0 as c_int,
+        y: 0 as c_int,
+        z: 0 as c_int,
+        bstate: 0 as u64,
+    }
+}
+
+fn clearline(line: i32) {
+    mv(line, 0);
+    clrtoeol();
+}
+
+/// Shows a message on the status line
+fn status(msg: &str) {
+    let line = LINES() - 2;
+    clearline(line);
+    mvaddstr(line, 0, msg);
+}
+
+/// Shows the state of the connection on the bottom line
+fn show_connection(msg: &str) {
+    let line = LINES() - 1;
+    clearline(line);
+    mvaddstr(line, 0, msg);
+}
+
+/// Asks a question on the bottom line, returning `default` if nothing is
+/// entered
+fn prompt(question: &str, default: &str) -> String {
+    let line = LINES() - 1;
+    clearline(line);
+    mvaddstr(line, 0, &format!("{} [{}]: ", question, default));
+    echo();
+    curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    let mut answer = String::new();
+    getnstr(&mut answer, 64);
+    noecho();
+    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    clearline(line);
+    match answer.trim() {
+        "" => String::from(default),
+        s => String::from(s),
+    }
+}
+
+fn draw_title() {
+    mvaddstr(0, 0, "╔═════════════════╗");
+    mvaddstr(1, 0, "║ Rusted Checkers ║");
+    mvaddstr(2, 0, "╚═════════════════╝");
+}
@@ -0,0 +1,401 @@
+//! Playing someone elsewhere, through a server or directly
+
+use crate::ui::chat::CHAT_HISTORY;
+use crate::ui::{clearline, prompt, show_connection, status, POLL_MS};
+
+use rustedcheckers::clock::TimeControl;
+use rustedcheckers::game::Game;
+use rustedcheckers::networking::client::{Connection, Link};
+use rustedcheckers::networking::peer::Peer;
+use rustedcheckers::networking::protocol::Message;
+use rustedcheckers::networking::Settings;
+use rustedcheckers::player::Player;
+
+use ncurses::*;
+
+use std::io;
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the server to answer a request from the lobby
+static REPLY_SECS: u64 = 5;
+/// How long to wait between attempts to reconnect
+static RETRY_SECS: u64 = 2;
+
+/// A game played against someone elsewhere, either through a server or
+/// directly
+pub struct Online {
+    conn: Box<dyn Link>,
+    /// Our colour, once the server has found us an opponent
+    seat: Option<Player>,
+    opponent: String,
+    /// The players of the game, if we are only watching it
+    pub watching: Option<(String, String)>,
+    /// The last state sent by the server, restored if a move is rejected
+    state: Game,
+    /// Whether a move has been sent and not yet answered
+    pending: bool,
+    over: bool,
+    lost: bool,
+    /// When to next try to reconnect, if the connection has dropped
+    retry: Option<Instant>,
+    /// What has been said in the game, oldest first
+    pub chat: Vec<String>,
+    /// When the clock in `state` was read
+    pub read: Instant,
+    /// The winner and how the game ended, until the game-over screen has
+    /// shown them
+    pub result: Option<(Option<Player>, String)>,
+}
+
+impl Online {
+    fn new(conn: Box<dyn Link>) -> Online {
+        Online {
+            conn,
+            seat: None,
+            opponent: String::new(),
+            watching: None,
+            state: Game::new(),
+            pending: false,
+            over: false,
+            lost: false,
+            retry: None,
+            chat: Vec::new(),
+            read: Instant::now(),
+            result: None,
+        }
+    }
+
+    /// Whether we may play a turn in `game`
+    pub fn my_turn(&self, game: &Game) -> bool {
+        !self.pending && !self.over && !self.lost && self.seat == Some(game.player)
+    }
+
+    pub fn describe(&self, game: &Game) -> String {
+        let peer = self.conn.peer_addr();
+        match (self.seat, &self.watching) {
+            _ if self.lost && self.retry.is_some() => format!("Reconnecting to {}...", peer),
+            _ if self.lost => format!("Disconnected from {}.", peer),
+            _ if self.over => format!("Connected to {}. The game is over.", peer),
+            (None, Some((black, white))) => {
+                format!("Connected to {}. Watching {} v. {}.", peer, black, white)
+            }
+            (None, None) => format!("Connected to {}. Waiting for an opponent...", peer),
+            (Some(seat), _) => {
+                let turn = if self.seat == Some(game.player) {
+                    String::from("Your turn.")
+                } else {
+                    format!("Waiting for {}.", self.opponent)
+                };
+                format!(
+                    "Connected to {}. Playing {:?} against {}. {}",
+                    peer, seat, self.opponent, turn
+                )
+            }
+        }
+    }
+
+    pub fn send(&mut self, m: &Message) {
+        if let Err(e) = self.conn.send(m) {
+            self.dropped(&e);
+        }
+    }
+
+    /// Notes that the connection has dropped, and tries to get it back if
+    /// the game is still going
+    fn dropped(&mut self, e: &io::Error) {
+        if !self.lost && !self.over {
+            self.retry = Some(Instant::now());
+            status(&format!("Connection lost: {}", e));
+        }
+        self.lost = true;
+    }
+
+    /// Tries to reconnect, if it is time to
+    fn reconnect(&mut self) {
+        match self.retry {
+            Some(at) if at <= Instant::now() => (),
+            _ => return,
+        }
+        match self.conn.reconnect() {
+            Ok(true) => {
+                self.lost = false;
+                self.retry = None;
+                status("Reconnected.");
+            }
+            Ok(false) => self.retry = None,
+            Err(e) => {
+                self.retry = Some(Instant::now() + Duration::from_secs(RETRY_SECS));
+                status(&format!("Could not reconnect: {}", e));
+            }
+        }
+    }
+
+    /// Sends the turn just completed in `game` to the server
+    pub fn submit(&mut self, game: &Game) {
+        if let Some(m) = game.history.last() {
+            self.pending = true;
+            self.send(&Message::MoveRequest(m.clone()));
+        }
+    }
+
+    /// Handles everything the server has sent since we last looked,
+    /// returning whether `game` has changed
+    pub fn poll(&mut self, game: &mut Game) -> bool {
+        let mut changed = false;
+        while let Some(m) = self.conn.poll() {
+            match m {
+                Ok(Message::SeatAssigned { player, opponent }) => {
+                    self.seat = Some(player);
+                    self.opponent = opponent;
+                }
+                Ok(Message::Watching { black, white }) => {
+                    self.watching = Some((black, white));
+                }
+                Ok(Message::State(state)) => {
+                    self.read = Instant::now();
+                    self.pending = false;
+                    self.state = (*state).clone();
+                    *game = *state;
+                    changed = true;
+                }
+                Ok(Message::MoveAccepted(m)) => {
+                    // The local game may already have moved on to the
+                    // other side, but the state it was played from has not
+                    let mover = self.state.player;
+                    status(&format!("{} played {}", self.state.name(mover), m));
+                }
+                Ok(Message::MoveRejected(reason)) => {
+                    self.pending = false;
+                    *game = self.state.clone();
+                    changed = true;
+                    status(&reason.to_string());
+                }
+                Ok(Message::GameOver { winner, reason }) => {
+                    self.over = true;
+                    match winner {
+                        Some(p) => status(&format!("{} {:?} wins.", reason, p)),
+                        None => status(&format!("{} The game is drawn.", reason)),
+                    }
+                    self.result = Some((winner, reason));
+                }
+                Ok(Message::PlayerAway { player, grace_ms }) => status(&format!(
+                    "{} has lost their connection, and has {} seconds to come back.",
+                    game.name(player),
+                    grace_ms / 1000
+                )),
+                Ok(Message::PlayerBack(player)) => {
+                    status(&format!("{} is back.", game.name(player)));
+                }
+                Ok(Message::ChatMessage { from, text }) => {
+                    if self.chat.len() == CHAT_HISTORY {
+                        self.chat.remove(0);
+                    }
+                    self.chat.push(format!("{}: {}", from, text));
+                }
+                Ok(Message::Refused(reason)) => status(&reason),
+                Ok(_) => (),
+                Err(e) => self.dropped(&e),
+            }
+        }
+        self.reconnect();
+        changed
+    }
+}
+
+/// Asks for the address of a server until one can be connected to, or
+/// returns `None` if the player gives up. The port offered is the one in
+/// `settings`, as is the address if it is an IPv4 address.
+pub fn connect_prompt(settings: &Settings, name: &str) -> Option<Online> {
+    let default_ip = match settings.host.parse::<Ipv4Addr>() {
+        Ok(ip) => ip.to_string(),
+        Err(_) => String::from("127.0.0.1"),
+    };
+    let default_port = settings.port.to_string();
+    loop {
+        let ip = prompt("Server IPv4 address, or q to quit", &default_ip);
+        if ip == "q" {
+            return None;
+        }
+        let ip = match ip.parse::<Ipv4Addr>() {
+            Ok(ip) => ip,
+            Err(_) => {
+                status(&format!("'{}' is not an IPv4 address.", ip));
+                continue;
+            }
+        };
+        let port = prompt("Port", &default_port);
+        let port = match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => {
+                status(&format!("'{}' is not a port.", port));
+                continue;
+            }
+        };
+        show_connection(&format!("Connecting to {}:{}...", ip, port));
+        refresh();
+        let mut conn = match Connection::open((ip, port), name) {
+            Ok(conn) => conn,
+            Err(e) => {
+                status(&format!("Could not connect to {}:{}: {}", ip, port, e));
+                continue;
+            }
+        };
+        status("");
+        match lobby(&mut conn) {
+            Ok(()) => return Some(Online::new(Box::new(conn))),
+            Err(e) => status(&e),
+        }
+    }
+}
+
+/// Waits for the server to answer a request made from the lobby
+fn wait_reply(conn: &mut Connection) -> Result<Message, String> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(REPLY_SECS) {
+        match conn.poll() {
+            Some(Ok(m)) => return Ok(m),
+            Some(Err(e)) => return Err(format!("Connection lost: {}", e)),
+            None => {
+                napms(POLL_MS);
+            }
+        }
+    }
+    Err(String::from("The server did not answer."))
+}
+
+/// Shows the games open on the server and asks which to join, until the
+/// player has chosen how to find an opponent
+fn lobby(conn: &mut Connection) -> Result<(), String> {
+    loop {
+        let (open, running) = match wait_reply(conn)? {
+            Message::Games { open, running } => (open, running),
+            Message::Refused(reason) => {
+                status(&reason);
+                continue;
+            }
+            Message::GameCreated(id) => {
+                clear();
+                status(&format!("Opened game {}.", id));
+                return Ok(());
+            }
+            Message::GameOver { reason, .. } => return Err(reason),
+            m => {
+                // The game we asked to join or watch has begun
+                conn.unread(m);
+                clear();
+                return Ok(());
+            }
+        };
+
+        for line in 0..(LINES() - 2) {
+            clearline(line);
+        }
+        let mut lines = vec![String::from("Open games:")];
+        for g in open.iter() {
+            lines.push(match g.color {
+                Some(p) => format!("  {}: {} playing {:?}", g.id, g.host, p),
+                None => format!("  {}: {}", g.id, g.host),
+            });
+        }
+        if open.is_empty() {
+            lines.push(String::from("  None"));
+        }
+        lines.push(String::from("Games in progress:"));
+        for g in running.iter() {
+            lines.push(format!("  {}: {} v. {}", g.id, g.black, g.white));
+        }
+        if running.is_empty() {
+            lines.push(String::from("  None"));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            mvaddstr(i as i32, 0, line);
+        }
+
+        let choice = prompt(
+            "Game to join, w and a game to watch, n for a new game, l to list again, or quick",
+            "quick",
+        );
+        let (watch, number) = match choice.strip_prefix('w') {
+            Some(number) => (true, number.trim()),
+            None => (false, choice.as_str()),
+        };
+        let request = match number {
+            "quick" => Message::QuickMatch,
+            "n" => Message::CreateGame(None),
+            "l" => Message::ListGames,
+            n => match n.parse() {
+                Ok(id) if watch => Message::Watch(id),
+                Ok(id) => Message::JoinGame(id),
+                Err(_) => {
+                    status(&format!("'{}' is not a game number.", n));
+                    Message::ListGames
+                }
+            },
+        };
+        conn.send(&request)
+            .map_err(|e| format!("Connection lost: {}", e))?;
+        if let Message::QuickMatch = request {
+            clear();
+            return Ok(());
+        }
+    }
+}
+
+/// Waits for someone to join a game hosted where `settings` say to listen,
+/// or returns `None` if the player presses q first
+pub fn host(
+    settings: &Settings,
+    name: &str,
+    color: Option<Player>,
+    time: Option<TimeControl>,
+) -> Result<Option<Online>, String> {
+    let (bind, port) = settings.bind_addr();
+    let listener = TcpListener::bind((bind, port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| format!("Could not listen on {}:{}: {}", bind, port, e))?;
+    show_connection(&format!(
+        "Waiting for someone to join on port {}, or press q to cancel...",
+        port
+    ));
+    refresh();
+    timeout(POLL_MS);
+    let hosted = loop {
+        match Peer::host(&listener, name, color, time) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            hosted => break hosted,
+        }
+        if let Some(WchResult::Char(c)) = wget_wch(stdscr()) {
+            if c == 'q' as u32 {
+                timeout(-1);
+                return Ok(None);
+            }
+        }
+    };
+    timeout(-1);
+    match hosted {
+        Ok(peer) => Ok(Some(Online::new(Box::new(peer)))),
+        Err(e) => Err(format!("Could not start the game: {}", e)),
+    }
+}
+
+/// Joins the game hosted at `addr`, on the port in `settings` if it does not
+/// give one
+pub fn join(
+    addr: &str,
+    settings: &Settings,
+    name: &str,
+    color: Option<Player>,
+) -> Result<Online, String> {
+    let addr = if addr.contains(':') {
+        String::from(addr)
+    } else {
+        format!("{}:{}", addr, settings.port)
+    };
+    show_connection(&format!("Joining {}...", addr));
+    refresh();
+    match Peer::join(addr.as_str(), name, color) {
+        Ok(peer) => Ok(Online::new(Box::new(peer))),
+        Err(e) => Err(format!("Could not join {}: {}", addr, e)),
+    }
+}
@@ -0,0 +1,587 @@
+//! Playing a game, from its first turn to the game-over screen
+
+use crate::ui::board::{draw_captured, draw_clock, draw_moves, draw_position, CaptureMarks, Look};
+use crate::ui::chat::ChatPane;
+use crate::ui::layout::{create_win, destroy_win, wait_for_room, Windows, TITLE_H, TITLE_POS};
+use crate::ui::online::{connect_prompt, host, join};
+use crate::ui::prefs::Prefs;
+use crate::ui::{new_mevent, show_connection, status, Mode, POLL_MS};
+
+use rustedcheckers::ai;
+use rustedcheckers::board::{Position, BOARD_WIDTH};
+use rustedcheckers::clock::{Clock, TimeControl};
+use rustedcheckers::game::Game;
+use rustedcheckers::moves::{Move, Submove};
+use rustedcheckers::networking::protocol::Message;
+use rustedcheckers::player::Player;
+use rustedcheckers::save;
+
+use ncurses::*;
+
+use std::char;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The winner of a local game and why, if it has been won
+fn result(game: &Game) -> Option<(Option<Player>, String)> {
+    game.win_condition()
+        .map(|w| (Some(w), format!("{} cannot move.", game.name(w.switch()))))
+}
+
+/// Takes the time `mover` spent on the turn just played off their clock,
+/// returning the result of the game if that or the turn has ended it
+fn press_clock(game: &mut Game, mover: Player, took: Duration) -> Option<(Option<Player>, String)> {
+    let in_time = match game.clock.as_mut() {
+        Some(clock) => clock.press(mover, took),
+        None => true,
+    };
+    if !in_time {
+        let reason = format!("{} ran out of time.", game.name(mover));
+        return Some((Some(mover.switch()), reason));
+    }
+    result(game)
+}
+
+/// What to do once a game is over
+enum AfterGame {
+    /// Play again, swapping colours if `true`
+    Rematch(bool),
+    /// Go back to the finished game
+    Look,
+    Quit,
+}
+
+/// Shows who won and why over the board, and asks what to do next. The game
+/// may be saved to `save_path` from here, along with the side `computer`
+/// plays. Rematches are only offered if `local`.
+fn game_over(
+    game: &Game,
+    winner: Option<Player>,
+    reason: &str,
+    save_path: &Path,
+    local: bool,
+    computer: Option<Player>,
+) -> AfterGame {
+    let result = match winner {
+        Some(p) => format!("{} ({:?}) wins.", game.name(p), p),
+        None => String::from("The game is drawn."),
+    };
+    let mut lines = vec![String::from(reason), result, String::new()];
+    if local {
+        lines.push(String::from("r    Rematch"));
+        lines.push(String::from("c    Rematch, swapping colours"));
+    }
+    lines.push(String::from("s    Save the game"));
+    lines.push(String::from("q    Quit"));
+    lines.push(String::from("Esc  Look at the board"));
+
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let width = (longest as i32 + 4).min(COLS());
+    let height = lines.len() as i32 + 4;
+    let win = create_win(height, width, TITLE_POS.0 + TITLE_H + 1, 1);
+    mvwaddstr(win, 0, 1, "┤Game over├");
+    for (i, line) in lines.iter().enumerate() {
+        mvwaddstr(win, i as i32 + 1, 2, line);
+    }
+    wrefresh(win);
+
+    let after = loop {
+        let c = match wget_wch(stdscr()) {
+            Some(WchResult::Char(c)) => char::from_u32(c),
+            _ => None,
+        };
+        match c {
+            Some('r') if local => break AfterGame::Rematch(false),
+            Some('c') if local => break AfterGame::Rematch(true),
+            Some('s') => {
+                let saved = match save::save(game, computer, save_path) {
+                    Ok(()) => format!("Saved to {}", save_path.display()),
+                    Err(e) => e,
+                };
+                let saved: String = saved.chars().take(width as usize - 4).collect();
+                mvwaddstr(
+                    win,
+                    height - 2,
+                    2,
+                    &format!("{:1$}", saved, width as usize - 4),
+                );
+                wrefresh(win);
+            }
+            Some('q') => break AfterGame::Quit,
+            Some('\u{1b}') => break AfterGame::Look,
+            _ => (),
+        }
+    };
+    destroy_win(win);
+    after
+}
+
+/// Moves the keyboard cursor by the given rows and columns, stopping at the
+/// edge of the board
+fn step(cursor: Position, rows: i32, cols: i32) -> Position {
+    let clamp = |n: usize, by: i32| (n as i32 + by).max(0).min(BOARD_WIDTH as i32 - 1) as usize;
+    Position::new(clamp(cursor.0, rows), clamp(cursor.1, cols))
+}
+
+/// The movable piece after the one at `cursor`, or before it if `back`, in
+/// the order of their squares
+fn next_piece(game: &Game, cursor: Position, back: bool) -> Option<Position> {
+    let pieces = game.movable_pieces();
+    if pieces.is_empty() {
+        return None;
+    }
+    let n = pieces.len();
+    let i = match pieces.iter().position(|&p| p == cursor) {
+        Some(i) if back => (i + n - 1) % n,
+        Some(i) => (i + 1) % n,
+        None if back => n - 1,
+        None => 0,
+    };
+    Some(pieces[i])
+}
+
+/// Selects the piece at `pos`, or moves the selected piece there if it may,
+/// as clicking the square does. Returns whether a turn has been finished.
+fn play_square(game: &mut Game, pos: Position) -> Result<bool, String> {
+    match game.selected {
+        Some(from) if game.hilighted.contains(&pos) => {
+            game.do_submove(&Submove { from, to: pos })?;
+            Ok(game.turn.is_none())
+        }
+        _ => {
+            game.select(pos);
+            Ok(false)
+        }
+    }
+}
+
+/// Saves the game, and the side `computer` plays, so that it may be resumed
+/// after a crash or an accidental quit
+fn autosave(game: &Game, computer: Option<Player>) {
+    if let Err(e) = save::save(game, computer, save::autosave_path()) {
+        status(&e);
+    }
+}
+
+/// A new local game, played with `time` on the clock. If the computer plays
+/// one side the player's name is given to the other.
+pub fn new_game(prefs: &Prefs, time: Option<TimeControl>, computer: Option<Player>) -> Game {
+    let mut game = Game::new();
+    if let Some(computer) = computer {
+        let (theirs, yours) = match computer {
+            Player::Black => (&mut game.black_name, &mut game.white_name),
+            Player::White => (&mut game.white_name, &mut game.black_name),
+        };
+        *theirs = String::from("Computer");
+        *yours = prefs.name.clone();
+    }
+    game.clock = time.map(Clock::new);
+    game
+}
+
+/// Runs a game until the player quits, saving to `save_path` when asked to.
+/// Unless the game is local, the player is first connected to their
+/// opponent. ncurses is expected to have been started.
+pub fn play(mut game: Game, save_path: &Path, mode: Mode, prefs: &Prefs) -> Result<(), String> {
+    let (local, mut computer) = match mode {
+        Mode::Local => (true, None),
+        Mode::Computer(p) => (true, Some(p)),
+        _ => (false, None),
+    };
+
+    clear();
+    refresh();
+    let connected = match mode {
+        Mode::Local | Mode::Computer(_) => Ok(None),
+        Mode::Connect(settings) => Ok(connect_prompt(&settings, &prefs.name)),
+        Mode::Host(settings, color, time) => host(&settings, &prefs.name, color, time),
+        Mode::Join(addr, settings, color) => join(&addr, &settings, &prefs.name, color).map(Some),
+    };
+    let mut online = match connected {
+        Ok(None) if !local => return Ok(()),
+        Ok(online) => online,
+        Err(e) => return Err(e),
+    };
+    // Lay the windows out to fit the terminal
+    let layout = match wait_for_room(online.is_some()) {
+        Some(layout) => layout,
+        None => return Ok(()),
+    };
+    let mut wins = Windows::new(layout);
+
+    // Draw the board, with the keyboard cursor on the first piece which may
+    // be moved
+    let mut cursor = next_piece(&game, Position::new(0, 0), false)
+        .unwrap_or_else(|| Position::new(BOARD_WIDTH - 1, 0));
+    let mut look = Look::new(prefs);
+    let mut marks = CaptureMarks::new(&game);
+    draw_position(&wins, &mut game, None, cursor, look, false);
+
+    // Draw the move list. While looking back through the game, `view` is
+    // how many turns in the board is shown.
+    let mut view: Option<usize> = None;
+    draw_moves(wins.moves, &game, game.history.len());
+
+    draw_captured(wins.captured, &game.board);
+
+    // Draw the clock. The turn being played locally started now.
+    let mut turn_started = Instant::now();
+    // Whether a local game has been won
+    let mut finished = false;
+    // The computer's search for its next turn, which runs on its own
+    // thread so that the clock keeps ticking while it thinks
+    let mut search: Option<Receiver<Option<Move>>> = None;
+    draw_clock(wins.clock, &game, Duration::from_secs(0));
+
+    // Create chat window
+    let mut chat = online.as_ref().map(|_| ChatPane::new(layout.chat));
+
+    status("Move with the arrow keys or hjkl, Enter to select, Tab for the next piece, or type a move like 11-15.");
+    refresh();
+
+    // A move being typed in notation
+    let mut notation = String::new();
+
+    // The winner and how the game ended, once it has. A local game may have
+    // been saved after it was won.
+    let mut ended = match online {
+        Some(_) => None,
+        None => result(&game),
+    };
+
+    // Input
+    'main: loop {
+        // Keep checking the network, keep the clock ticking, and take the
+        // capture marks away in time
+        match online.is_some() || game.clock.is_some() || marks.showing() || search.is_some() {
+            true => timeout(POLL_MS),
+            _ => timeout(-1),
+        };
+
+        // An ending not yet shown is shown, and the computer starts
+        // thinking, before waiting for a key
+        let ch = match ended {
+            Some(_) => None,
+            None if computer == Some(game.player) && !finished && search.is_none() => None,
+            None => wget_wch(stdscr()),
+        };
+        // While a message is being typed every key goes to it
+        let ch = match (chat.as_mut(), online.as_mut()) {
+            (Some(pane), Some(o)) if pane.typing() => {
+                if let Some(text) = pane.edit(ch) {
+                    o.send(&Message::Chat(text));
+                }
+                None
+            }
+            _ => ch,
+        };
+
+        // The square clicked or chosen with the cursor, or the move typed
+        let mut target = None;
+        let mut typed = None;
+        let typing = !notation.is_empty();
+        let viewed = view;
+        let mut resized = false;
+        // Keys and clicks are taken as on the board as drawn
+        let side = look.orientation.side(match view {
+            Some(n) => game.position_after(n).player,
+            None => game.player,
+        });
+        let seen = |cursor: Position, rows, cols| step(cursor.norm(side), rows, cols).norm(side);
+        match ch {
+            Some(WchResult::KeyCode(KEY_RESIZE)) => resized = true,
+            Some(WchResult::KeyCode(KEY_MOUSE)) => {
+                // Get stdscr coords
+                let mut mevent = new_mevent();
+                let err_code = getmouse(&mut mevent);
+                if err_code != 0 {
+                    panic!("Couldn't get mouse event.");
+                }
+                // Normalize to the board window, and find the square
+                let xs: &mut [i32] = &mut [mevent.x];
+                let ys: &mut [i32] = &mut [mevent.y];
+                if wmouse_trafo(wins.board, ys, xs, false) {
+                    let (h, w) = wins.layout.cell;
+                    let (y, x) = (ys[0] - 1, xs[0] - 1);
+                    let n = BOARD_WIDTH as i32;
+                    if y >= 0 && x >= 0 && y / h < n && x / w < n {
+                        cursor = Position::new((y / h) as usize, (x / w) as usize).norm(side);
+                        target = Some(cursor);
+                    }
+                }
+            }
+            Some(WchResult::KeyCode(KEY_UP)) => cursor = seen(cursor, -1, 0),
+            Some(WchResult::KeyCode(KEY_DOWN)) => cursor = seen(cursor, 1, 0),
+            Some(WchResult::KeyCode(KEY_LEFT)) => cursor = seen(cursor, 0, -1),
+            Some(WchResult::KeyCode(KEY_RIGHT)) => cursor = seen(cursor, 0, 1),
+            Some(WchResult::Char(9)) | Some(WchResult::KeyCode(KEY_BTAB)) => {
+                let back = matches!(ch, Some(WchResult::KeyCode(KEY_BTAB)));
+                if let Some(p) = next_piece(&game, cursor, back) {
+                    cursor = p;
+                    game.select(p);
+                }
+            }
+            Some(WchResult::Char(10))
+            | Some(WchResult::Char(13))
+            | Some(WchResult::KeyCode(KEY_ENTER)) => {
+                if typing {
+                    typed = Some(notation.split_off(0));
+                } else {
+                    target = Some(cursor);
+                }
+            }
+            Some(WchResult::Char(8))
+            | Some(WchResult::Char(127))
+            | Some(WchResult::KeyCode(KEY_BACKSPACE)) => {
+                notation.pop();
+                status(&format!("Move: {}", notation));
+            }
+            Some(WchResult::Char(27)) if typing => {
+                notation.clear();
+                status("");
+            }
+            Some(WchResult::KeyCode(KEY_HOME)) => view = Some(0),
+            Some(WchResult::KeyCode(KEY_END)) => view = None,
+            Some(WchResult::KeyCode(KEY_PPAGE)) => {
+                if let Some(pane) = chat.as_mut() {
+                    let page = pane.rows();
+                    pane.scroll_by(page);
+                }
+            }
+            Some(WchResult::KeyCode(KEY_NPAGE)) => {
+                if let Some(pane) = chat.as_mut() {
+                    let page = pane.rows();
+                    pane.scroll_by(-page);
+                }
+            }
+            Some(WchResult::Char(c)) => match char::from_u32(c).unwrap_or(' ') {
+                c @ '0'..='9' | c @ '-' | c @ 'x' if typing || c.is_ascii_digit() => {
+                    notation.push(c);
+                    status(&format!("Move: {}", notation));
+                }
+                ' ' => target = Some(cursor),
+                '[' => {
+                    let n = view.unwrap_or(game.history.len());
+                    view = Some(n.saturating_sub(1));
+                }
+                ']' => {
+                    view = view.map(|n| n + 1).filter(|&n| n < game.history.len());
+                }
+                'k' => cursor = seen(cursor, -1, 0),
+                'j' => cursor = seen(cursor, 1, 0),
+                'h' => cursor = seen(cursor, 0, -1),
+                'l' => cursor = seen(cursor, 0, 1),
+                'o' => {
+                    look.orientation = look.orientation.next();
+                    status(&format!(
+                        "Showing the board from {}.",
+                        look.orientation.name()
+                    ));
+                }
+                'c' => {
+                    look.labels = look.labels.next();
+                    status(&format!("Labels: {}.", look.labels.name()));
+                }
+                'q' => {
+                    if online.is_none() {
+                        autosave(&game, computer);
+                    }
+                    break 'main;
+                }
+                's' => match save::save(&game, computer, save_path) {
+                    Ok(()) => status(&format!("Saved to {}", save_path.display())),
+                    Err(e) => status(&e),
+                },
+                't' => match (&online, chat.as_mut()) {
+                    (Some(o), _) if o.watching.is_some() => {
+                        status("You are only watching this game.");
+                    }
+                    (_, Some(pane)) => pane.start(),
+                    _ => (),
+                },
+                _ => (),
+            },
+            _ => (),
+        }
+
+        if view != viewed {
+            match view {
+                Some(n) => status(&format!("After turn {} of {}.", n, game.history.len())),
+                None => status(""),
+            }
+        }
+
+        if target.is_some() || typed.is_some() {
+            let (waiting, watching) = match &online {
+                Some(o) => (!o.my_turn(&game), o.watching.is_some()),
+                None => (computer == Some(game.player), false),
+            };
+            let played = if finished {
+                Err(String::from("The game is over."))
+            } else if view.is_some() {
+                Err(String::from(
+                    "You are looking back through the game. Press End to return.",
+                ))
+            } else if watching {
+                Err(String::from("You are only watching this game."))
+            } else if waiting {
+                Err(String::from("Please wait for your turn."))
+            } else if let Some(s) = typed {
+                status("");
+                game.parse_move(&s).and_then(|m| game.do_move(&m))
+            } else {
+                play_square(&mut game, cursor)
+            };
+            match played {
+                Ok(true) => {
+                    match online.as_mut() {
+                        Some(o) => o.submit(&game),
+                        None => {
+                            let mover = game.player.switch();
+                            ended = press_clock(&mut game, mover, turn_started.elapsed());
+                            turn_started = Instant::now();
+                            autosave(&game, computer);
+                        }
+                    }
+                    draw_captured(wins.captured, &game.board);
+                }
+                Ok(false) => (),
+                Err(e) => status(&e),
+            }
+        }
+        let mut changed = marks.update(&game) || ch.is_some();
+
+        if resized {
+            match wait_for_room(chat.is_some()) {
+                Some(layout) => {
+                    wins.close();
+                    wins = Windows::new(layout);
+                    if let Some(pane) = chat.as_mut() {
+                        pane.place(layout.chat);
+                    }
+                    draw_captured(wins.captured, &game.board);
+                }
+                None => {
+                    if online.is_none() {
+                        autosave(&game, computer);
+                    }
+                    break 'main;
+                }
+            }
+        }
+
+        if computer == Some(game.player) && !finished && ended.is_none() && search.is_none() {
+            // Show the turn just played while the computer thinks
+            marks.update(&game);
+            draw_position(&wins, &mut game, view, cursor, look, marks.showing());
+            draw_moves(wins.moves, &game, view.unwrap_or(game.history.len()));
+            status("Thinking...");
+            refresh();
+            let (tx, rx) = channel();
+            let position = game.clone();
+            let depth = ai::depth(prefs.level);
+            thread::spawn(move || tx.send(ai::best_move(&position, depth)));
+            search = Some(rx);
+        }
+        let searched = match search.as_ref().map(Receiver::try_recv) {
+            Some(Err(TryRecvError::Empty)) | None => None,
+            Some(done) => {
+                search = None;
+                done.ok().flatten()
+            }
+        };
+        if let Some(m) = searched {
+            let mover = game.player;
+            match game.do_move(&m) {
+                Ok(_) => {
+                    ended = press_clock(&mut game, mover, turn_started.elapsed());
+                    turn_started = Instant::now();
+                    autosave(&game, computer);
+                    status(&format!("{} played {}", game.name(mover), m));
+                }
+                Err(e) => status(&e),
+            }
+            draw_captured(wins.captured, &game.board);
+            changed = true;
+        }
+
+        if let Some(o) = online.as_mut() {
+            if o.poll(&mut game) {
+                changed = true;
+                draw_captured(wins.captured, &game.board);
+            }
+            if let Some(result) = o.result.take() {
+                ended = Some(result);
+            }
+            show_connection(&o.describe(&game));
+            refresh();
+        }
+        let thinking = match &online {
+            Some(o) => o.read.elapsed(),
+            None => turn_started.elapsed(),
+        };
+        if online.is_none() && !finished && game.out_of_time(thinking) {
+            let loser = game.player;
+            let reason = format!("{} ran out of time.", game.name(loser));
+            status(&format!("{} {:?} wins.", reason, loser.switch()));
+            ended = Some((Some(loser.switch()), reason));
+        }
+        draw_clock(wins.clock, &game, thinking);
+
+        if let Some((winner, reason)) = ended.take() {
+            // The computer may have run out of time while still thinking
+            search = None;
+            finished = online.is_none();
+            view = None;
+            marks.update(&game);
+            draw_position(&wins, &mut game, view, cursor, look, marks.showing());
+            draw_moves(wins.moves, &game, game.history.len());
+            match game_over(&game, winner, &reason, save_path, local, computer) {
+                AfterGame::Rematch(swap) => {
+                    game = game.rematch(swap);
+                    if swap {
+                        computer = computer.map(|p| p.switch());
+                    }
+                    finished = false;
+                    turn_started = Instant::now();
+                    cursor = next_piece(&game, Position::new(0, 0), false).unwrap_or(cursor);
+                    autosave(&game, computer);
+                    draw_captured(wins.captured, &game.board);
+                    draw_clock(wins.clock, &game, Duration::from_secs(0));
+                    status(&format!(
+                        "New game: {} plays Black and {} plays White.",
+                        game.black_name, game.white_name
+                    ));
+                }
+                AfterGame::Look => (),
+                AfterGame::Quit => {
+                    if online.is_none() {
+                        autosave(&game, computer);
+                    }
+                    break 'main;
+                }
+            }
+            // Uncover whatever the game-over screen was drawn over
+            wins.uncover();
+            changed = true;
+        }
+        if marks.update(&game) || changed {
+            draw_position(&wins, &mut game, view, cursor, look, marks.showing());
+            draw_moves(wins.moves, &game, view.unwrap_or(game.history.len()));
+        }
+        // Drawn last, to leave the cursor where the message is typed
+        if let (Some(pane), Some(o)) = (chat.as_mut(), online.as_ref()) {
+            pane.draw(&o.chat);
+        }
+    }
+
+    if let Some(pane) = chat {
+        destroy_win(pane.win);
+    }
+    wins.close();
+    timeout(-1);
+    clear();
+    Ok(())
+}
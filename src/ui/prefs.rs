@@ -0,0 +1,398 @@
+//! The player's preferences, kept between games
+//!
+//! Preferences are written to a JSON config file, e.g.
+//! `{"name":"eric","color":"Black","time":null,"level":2,"theme":"Classic"}`,
+//! along with how the board is shown. Anything missing
+//! from the file takes its default, so files written by older versions still
+//! load.
+
+use rustedcheckers::clock::TimeControl;
+use rustedcheckers::player::Player;
+use rustedcheckers::save;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The computer's level of play unless another is chosen
+pub const DEFAULT_LEVEL: u32 = 2;
+
+/// The colours the game is drawn in
+///
+/// Serialized as the name of the theme, e.g. `"HighContrast"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    /// Red and blue pieces on a cream background
+    #[default]
+    Classic,
+    /// Bright pieces on black, for dim screens and weak eyesight
+    HighContrast,
+    /// Orange and blue pieces, which can be told apart with any kind of
+    /// colour blindness
+    ColourBlind,
+    /// No colour at all, as on terminals which cannot show it
+    Monochrome,
+}
+
+impl Theme {
+    /// Every theme, in the order they are offered
+    pub const ALL: [Theme; 4] = [
+        Theme::Classic,
+        Theme::HighContrast,
+        Theme::ColourBlind,
+        Theme::Monochrome,
+    ];
+
+    /// The theme after this one, wrapping around to the first
+    pub fn next(self) -> Theme {
+        after(&Theme::ALL, self)
+    }
+
+    /// The theme's name, as shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::HighContrast => "high contrast",
+            Theme::ColourBlind => "colour-blind friendly",
+            Theme::Monochrome => "monochrome",
+        }
+    }
+}
+
+/// Which side of the board is drawn nearest the player
+///
+/// Serialized as the name of the side, e.g. `"ToMove"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Orientation {
+    /// Black's side, whoever is to move
+    #[default]
+    Black,
+    /// White's side, whoever is to move
+    White,
+    /// The side of whoever is to move, turning the board every turn
+    ToMove,
+}
+
+impl Orientation {
+    /// Every orientation, in the order they are offered
+    pub const ALL: [Orientation; 3] = [Orientation::Black, Orientation::White, Orientation::ToMove];
+
+    /// The orientation after this one, wrapping around to the first
+    pub fn next(self) -> Orientation {
+        after(&Orientation::ALL, self)
+    }
+
+    /// The side the board is seen from, when `to_move` is to move
+    pub fn side(self, to_move: Player) -> Player {
+        match self {
+            Orientation::Black => Player::Black,
+            Orientation::White => Player::White,
+            Orientation::ToMove => to_move,
+        }
+    }
+
+    /// The orientation's name, as shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::Black => "Black's side",
+            Orientation::White => "White's side",
+            Orientation::ToMove => "the side to move",
+        }
+    }
+}
+
+/// What the squares of the board are labelled with
+///
+/// Serialized as the name of the labels, e.g. `"Squares"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Labels {
+    /// Nothing, leaving bare squares
+    #[default]
+    None,
+    /// Rows numbered 1 to 8 and columns lettered a to h, from Black's side
+    RowsAndColumns,
+    /// The standard notation square numbers, 1 to 32
+    Squares,
+}
+
+impl Labels {
+    /// Every kind of label, in the order they are offered
+    pub const ALL: [Labels; 3] = [Labels::None, Labels::RowsAndColumns, Labels::Squares];
+
+    /// The labels after these, wrapping around to the first
+    pub fn next(self) -> Labels {
+        after(&Labels::ALL, self)
+    }
+
+    /// The labels' name, as shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Labels::None => "none",
+            Labels::RowsAndColumns => "rows and columns",
+            Labels::Squares => "square numbers",
+        }
+    }
+}
+
+/// The characters pieces are drawn with
+///
+/// Serialized as the name of the set, e.g. `"Ascii"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Glyphs {
+    /// Discs, with kings drawn as two discs side by side
+    #[default]
+    Discs,
+    /// Discs, with kings drawn as crowns
+    Crowns,
+    /// Plain ASCII, for terminals which cannot show Unicode
+    Ascii,
+}
+
+impl Glyphs {
+    /// Every set of glyphs, in the order they are offered
+    pub const ALL: [Glyphs; 3] = [Glyphs::Discs, Glyphs::Crowns, Glyphs::Ascii];
+
+    /// The set after this one, wrapping around to the first
+    pub fn next(self) -> Glyphs {
+        after(&Glyphs::ALL, self)
+    }
+
+    /// How a man and a king are drawn, each as the two characters of a
+    /// board square
+    pub fn pieces(self) -> (&'static str, &'static str) {
+        match self {
+            Glyphs::Discs => ("⬤ ", "⬤⬤"),
+            Glyphs::Crowns => ("⬤ ", "♛ "),
+            Glyphs::Ascii => ("()", "[]"),
+        }
+    }
+
+    /// The set's name, as shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            Glyphs::Discs => "discs",
+            Glyphs::Crowns => "crowns",
+            Glyphs::Ascii => "ASCII",
+        }
+    }
+}
+
+/// How the last turn played is shown on the board
+///
+/// Serialized as the name of the style, e.g. `"Marks"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LastMove {
+    /// The squares the piece moved through are shaded, and the squares of
+    /// the pieces it captured are marked for a moment
+    #[default]
+    Shade,
+    /// The squares the piece left and passed through are dotted, the piece
+    /// is underlined, and captures are marked as for `Shade`
+    Marks,
+    /// Nothing is shown
+    Off,
+}
+
+impl LastMove {
+    /// Every style, in the order they are offered
+    pub const ALL: [LastMove; 3] = [LastMove::Shade, LastMove::Marks, LastMove::Off];
+
+    /// The style after this one, wrapping around to the first
+    pub fn next(self) -> LastMove {
+        after(&LastMove::ALL, self)
+    }
+
+    /// The style's name, as shown to the player
+    pub fn name(self) -> &'static str {
+        match self {
+            LastMove::Shade => "shaded",
+            LastMove::Marks => "marked",
+            LastMove::Off => "not shown",
+        }
+    }
+}
+
+/// The choice after `current` in `all`, wrapping around to the first
+fn after<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+    let i = all.iter().position(|&t| t == current).unwrap_or(0);
+    all[(i + 1) % all.len()]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Prefs {
+    /// The name to play under
+    pub name: String,
+    /// The colour to ask for, if any
+    pub color: Option<Player>,
+    /// The time control new games are played with, if they are timed
+    pub time: Option<TimeControl>,
+    /// How well the computer plays, from 1 to the number of
+    /// [`ai::LEVELS`](../ai/constant.LEVELS.html)
+    pub level: u32,
+    /// The colours to draw the game in
+    pub theme: Theme,
+    /// Which side the board is seen from
+    pub orientation: Orientation,
+    /// What the squares are labelled with
+    pub labels: Labels,
+    /// The characters pieces are drawn with
+    pub glyphs: Glyphs,
+    /// How the last turn played is shown
+    pub last_move: LastMove,
+}
+
+impl Default for Prefs {
+    fn default() -> Prefs {
+        Prefs {
+            name: env::var("USER").unwrap_or_else(|_| String::from("Anonymous")),
+            color: None,
+            time: None,
+            level: DEFAULT_LEVEL,
+            theme: Theme::default(),
+            orientation: Orientation::default(),
+            labels: Labels::default(),
+            glyphs: Glyphs::default(),
+            last_move: LastMove::default(),
+        }
+    }
+}
+
+/// The directory the config file is kept in. This follows the XDG base
+/// directory specification, falling back to the working directory.
+pub fn config_dir() -> PathBuf {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir).join("rustedcheckers"),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config/rustedcheckers"),
+            None => PathBuf::from("."),
+        },
+    }
+}
+
+/// Where preferences are kept unless another file is given
+pub fn default_path() -> PathBuf {
+    config_dir().join("config.json")
+}
+
+impl Prefs {
+    /// Reads the preferences in `path`, or the defaults if there is no such
+    /// file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Prefs, String> {
+        let path = path.as_ref();
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Prefs::default()),
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&json)
+            .map_err(|e| format!("{} is not a config file: {}", path.display(), e))
+    }
+
+    /// Writes the preferences to `path` as JSON, creating its directory if
+    /// need be
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Could not serialize preferences: {}", e))?;
+        save::write(path.as_ref(), &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file in the temporary directory which no other test or test run
+    /// uses, for the test to remove once it is done
+    fn tmp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "rustedcheckers-prefs-test-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    #[test]
+    fn save_load() {
+        let path = tmp_path("save_load.json");
+        let prefs = Prefs {
+            name: String::from("Daniel"),
+            color: Some(Player::White),
+            time: Some("5+3".parse().unwrap()),
+            level: 4,
+            theme: Theme::ColourBlind,
+            orientation: Orientation::ToMove,
+            labels: Labels::Squares,
+            glyphs: Glyphs::Ascii,
+            last_move: LastMove::Marks,
+        };
+        prefs.save(&path).unwrap();
+        assert_eq!(Prefs::load(&path), Ok(prefs));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_missing() {
+        assert_eq!(Prefs::load(tmp_path("missing.json")), Ok(Prefs::default()));
+    }
+
+    #[test]
+    fn load_partial() {
+        let path = tmp_path("load_partial.json");
+        fs::write(&path, r#"{"name":"David"}"#).unwrap();
+        let prefs = Prefs::load(&path).unwrap();
+        assert_eq!(prefs.name, "David");
+        assert_eq!(prefs.level, DEFAULT_LEVEL);
+        assert_eq!(prefs.theme, Theme::Classic);
+        assert_eq!(prefs.orientation, Orientation::Black);
+        assert_eq!(prefs.labels, Labels::None);
+        assert_eq!(prefs.last_move, LastMove::Shade);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn themes() {
+        let mut theme = Theme::Classic;
+        for _ in 0..Theme::ALL.len() - 1 {
+            theme = theme.next();
+            assert_ne!(theme, Theme::Classic);
+        }
+        assert_eq!(theme.next(), Theme::Classic);
+        assert_eq!(
+            serde_json::to_string(&Theme::HighContrast).unwrap(),
+            r#""HighContrast""#
+        );
+    }
+
+    #[test]
+    fn orientation() {
+        assert_eq!(Orientation::Black.side(Player::White), Player::Black);
+        assert_eq!(Orientation::White.side(Player::Black), Player::White);
+        assert_eq!(Orientation::ToMove.side(Player::White), Player::White);
+        assert_eq!(Orientation::ToMove.next(), Orientation::Black);
+        assert_eq!(Labels::Squares.next(), Labels::None);
+    }
+
+    #[test]
+    fn glyphs() {
+        for glyphs in Glyphs::ALL.iter() {
+            let (man, king) = glyphs.pieces();
+            assert_ne!(man, king);
+            assert_eq!(man.chars().count(), 2);
+            assert_eq!(king.chars().count(), 2);
+        }
+        let (man, king) = Glyphs::Ascii.pieces();
+        assert!(man.is_ascii() && king.is_ascii());
+    }
+
+    #[test]
+    fn load_invalid() {
+        let path = tmp_path("load_invalid.json");
+        fs::write(&path, "level = 3").unwrap();
+        assert!(Prefs::load(&path)
+            .unwrap_err()
+            .contains("not a config file"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+//! The colours and glyphs the game is drawn in
+
+use crate::ui::prefs::{Glyphs, Prefs, Theme};
+
+use rustedcheckers::piece::Piece;
+use rustedcheckers::player::Player;
+
+use ncurses::constants::{
+    COLOR_BLACK, COLOR_BLUE, COLOR_CYAN, COLOR_GREEN, COLOR_MAGENTA, COLOR_RED, COLOR_WHITE,
+    COLOR_YELLOW,
+};
+use ncurses::*;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The colours of a theme
+struct Palette {
+    /// Text, and the background of every window
+    foreground: i16,
+    background: i16,
+    /// White's pieces
+    white: i16,
+    /// Black's pieces
+    black: i16,
+    /// The squares
+    light: i16,
+    dark: i16,
+    /// Behind the selected piece
+    selected: i16,
+    /// The squares the selected piece can move to
+    target: i16,
+    /// The square under the keyboard cursor
+    cursor: i16,
+    /// Behind the squares the last turn passed through
+    last: i16,
+    /// The marks left where pieces were just captured
+    captured: i16,
+}
+
+// Each theme's palette for terminals with 256 colours, then for those with 8
+static CLASSIC: [Palette; 2] = [
+    Palette {
+        foreground: COLOR_BLACK,
+        background: 230,
+        white: 9,
+        black: 12,
+        light: COLOR_WHITE,
+        dark: COLOR_BLACK,
+        selected: 3,
+        target: 3,
+        cursor: 14,
+        last: 238,
+        captured: 196,
+    },
+    Palette {
+        foreground: COLOR_BLACK,
+        background: COLOR_WHITE,
+        white: COLOR_RED,
+        black: COLOR_BLUE,
+        light: COLOR_WHITE,
+        dark: COLOR_BLACK,
+        selected: COLOR_YELLOW,
+        target: COLOR_YELLOW,
+        cursor: COLOR_CYAN,
+        last: COLOR_GREEN,
+        captured: COLOR_MAGENTA,
+    },
+];
+static HIGH_CONTRAST: [Palette; 2] = [
+    Palette {
+        foreground: 15,
+        background: 16,
+        white: 226,
+        black: 51,
+        light: 15,
+        dark: 16,
+        selected: 201,
+        target: 46,
+        cursor: 196,
+        last: 240,
+        captured: 196,
+    },
+    Palette {
+        foreground: COLOR_WHITE,
+        background: COLOR_BLACK,
+        white: COLOR_YELLOW,
+        black: COLOR_CYAN,
+        light: COLOR_WHITE,
+        dark: COLOR_BLACK,
+        selected: COLOR_MAGENTA,
+        target: COLOR_GREEN,
+        cursor: COLOR_RED,
+        last: COLOR_BLUE,
+        captured: COLOR_RED,
+    },
+];
+// Orange, sky blue, yellow, bluish green and reddish purple can be told apart
+// with any kind of colour blindness
+static COLOUR_BLIND: [Palette; 2] = [
+    Palette {
+        foreground: COLOR_BLACK,
+        background: 230,
+        white: 208,
+        black: 39,
+        light: COLOR_WHITE,
+        dark: COLOR_BLACK,
+        selected: 220,
+        target: 36,
+        cursor: 175,
+        last: 239,
+        captured: 15,
+    },
+    Palette {
+        foreground: COLOR_BLACK,
+        background: COLOR_WHITE,
+        white: COLOR_YELLOW,
+        black: COLOR_BLUE,
+        light: COLOR_WHITE,
+        dark: COLOR_BLACK,
+        selected: COLOR_CYAN,
+        target: COLOR_CYAN,
+        cursor: COLOR_MAGENTA,
+        last: COLOR_GREEN,
+        captured: COLOR_WHITE,
+    },
+];
+
+/// Whether the game is being drawn in colour. Without it, squares and pieces
+/// are told apart by attributes and letters instead.
+static IN_COLOUR: AtomicBool = AtomicBool::new(false);
+/// Whether the terminal's locale can show Unicode
+static UNICODE: AtomicBool = AtomicBool::new(false);
+/// The glyphs pieces are drawn with, as an index into `Glyphs::ALL`
+static GLYPHS: AtomicUsize = AtomicUsize::new(0);
+
+// Color Pairs
+pub static COLOR_PAIR_DEFAULT: i16 = 1;
+pub static COLOR_PAIR_RED_ON_BLACK: i16 = 2;
+pub static COLOR_PAIR_BLUE_ON_BLACK: i16 = 3;
+pub static COLOR_PAIR_RED_ON_WHITE: i16 = 4;
+pub static COLOR_PAIR_BLUE_ON_WHITE: i16 = 5;
+pub static COLOR_PAIR_WHITE: i16 = 6;
+pub static COLOR_PAIR_BLACK: i16 = 7;
+pub static COLOR_PAIR_RED_ON_BKGD: i16 = 8;
+pub static COLOR_PAIR_BLUE_ON_BKGD: i16 = 9;
+pub static COLOR_PAIR_RED_HI: i16 = 10;
+pub static COLOR_PAIR_BLUE_HI: i16 = 11;
+pub static COLOR_PAIR_EMPTY_HI: i16 = 12;
+pub static COLOR_PAIR_RED_CURSOR: i16 = 13;
+pub static COLOR_PAIR_BLUE_CURSOR: i16 = 14;
+pub static COLOR_PAIR_EMPTY_CURSOR: i16 = 15;
+pub static COLOR_PAIR_LABEL: i16 = 16;
+pub static COLOR_PAIR_RED_LAST: i16 = 17;
+pub static COLOR_PAIR_BLUE_LAST: i16 = 18;
+pub static COLOR_PAIR_EMPTY_LAST: i16 = 19;
+pub static COLOR_PAIR_CAPTURED: i16 = 20;
+
+/// Starts ncurses, with the input the game uses and the colours and glyphs
+/// in `prefs`
+pub fn start_curses(prefs: &Prefs) {
+    // Unicode, in the terminal's own locale if it has it
+    let unicode = |locale: String| {
+        let locale = locale.to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    };
+    let has_unicode = unicode(setlocale(LcCategory::all, ""))
+        || unicode(setlocale(LcCategory::all, "en_US.UTF-8"));
+    UNICODE.store(has_unicode, Ordering::Relaxed);
+    set_glyphs(prefs.glyphs);
+
+    // ncurses init
+    initscr();
+    cbreak();
+    noecho();
+    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    keypad(stdscr(), true);
+
+    // Coloring
+    if has_colors() {
+        start_color();
+        use_default_colors();
+    }
+    set_theme(prefs.theme);
+
+    // Mouse
+    mousemask(ALL_MOUSE_EVENTS as mmask_t, None);
+
+    refresh();
+}
+
+/// The palette to draw `theme` in, if the terminal can show it
+fn palette(theme: Theme) -> Option<&'static Palette> {
+    let palettes = match theme {
+        _ if !has_colors() => return None,
+        Theme::Classic => &CLASSIC,
+        Theme::HighContrast => &HIGH_CONTRAST,
+        Theme::ColourBlind => &COLOUR_BLIND,
+        Theme::Monochrome => return None,
+    };
+    match COLORS() {
+        n if n >= 256 => Some(&palettes[0]),
+        _ => Some(&palettes[1]),
+    }
+}
+
+/// Sets up the colour pairs for `theme`, falling back to fewer colours or to
+/// none at all as the terminal requires
+pub fn set_theme(theme: Theme) {
+    let p = match palette(theme) {
+        Some(p) => p,
+        None => {
+            // Every pair is drawn in the terminal's own colours
+            if has_colors() {
+                for pair in COLOR_PAIR_DEFAULT..=COLOR_PAIR_CAPTURED {
+                    init_pair(pair, -1, -1);
+                }
+            }
+            IN_COLOUR.store(false, Ordering::Relaxed);
+            return;
+        }
+    };
+    init_pair(COLOR_PAIR_DEFAULT, p.foreground, p.background);
+    init_pair(COLOR_PAIR_RED_ON_BLACK, p.white, p.dark);
+    init_pair(COLOR_PAIR_BLUE_ON_BLACK, p.black, p.dark);
+    init_pair(COLOR_PAIR_RED_ON_WHITE, p.white, p.light);
+    init_pair(COLOR_PAIR_BLUE_ON_WHITE, p.black, p.light);
+    init_pair(COLOR_PAIR_WHITE, p.light, p.light);
+    init_pair(COLOR_PAIR_BLACK, p.dark, p.dark);
+    init_pair(COLOR_PAIR_BLUE_ON_BKGD, p.black, p.background);
+    init_pair(COLOR_PAIR_RED_ON_BKGD, p.white, p.background);
+    init_pair(COLOR_PAIR_RED_HI, p.white, p.selected);
+    init_pair(COLOR_PAIR_BLUE_HI, p.black, p.selected);
+    init_pair(COLOR_PAIR_EMPTY_HI, p.target, p.target);
+    init_pair(COLOR_PAIR_RED_CURSOR, p.white, p.cursor);
+    init_pair(COLOR_PAIR_BLUE_CURSOR, p.black, p.cursor);
+    init_pair(COLOR_PAIR_EMPTY_CURSOR, p.cursor, p.cursor);
+    init_pair(COLOR_PAIR_LABEL, p.light, p.dark);
+    init_pair(COLOR_PAIR_RED_LAST, p.white, p.last);
+    init_pair(COLOR_PAIR_BLUE_LAST, p.black, p.last);
+    init_pair(COLOR_PAIR_EMPTY_LAST, p.last, p.last);
+    init_pair(COLOR_PAIR_CAPTURED, p.captured, p.dark);
+    IN_COLOUR.store(true, Ordering::Relaxed);
+}
+
+pub fn in_colour() -> bool {
+    IN_COLOUR.load(Ordering::Relaxed)
+}
+
+/// Draws pieces with `glyphs`, or in ASCII if the terminal cannot show
+/// Unicode
+pub fn set_glyphs(glyphs: Glyphs) {
+    let glyphs = match UNICODE.load(Ordering::Relaxed) {
+        true => glyphs,
+        _ => Glyphs::Ascii,
+    };
+    let i = Glyphs::ALL.iter().position(|&g| g == glyphs).unwrap_or(0);
+    GLYPHS.store(i, Ordering::Relaxed);
+}
+
+/// How `piece` is drawn, two columns wide. Without colour, the players'
+/// pieces are told apart by letter instead.
+pub fn glyph(piece: Piece) -> &'static str {
+    let (man, king) = Glyphs::ALL[GLYPHS.load(Ordering::Relaxed)].pieces();
+    match (in_colour(), piece) {
+        (true, Piece::Normal(_)) => man,
+        (true, Piece::King(_)) => king,
+        (false, Piece::Normal(Player::White)) => "w ",
+        (false, Piece::Normal(Player::Black)) => "b ",
+        (false, Piece::King(Player::White)) => "WW",
+        (false, Piece::King(Player::Black)) => "BB",
+    }
+}
@@ -0,0 +1,57 @@
+use rustedcheckers::board::{Board, Position};
+use rustedcheckers::game::Game;
+use rustedcheckers::player::Player;
+
+/// Plays a turn written in standard notation, e.g. `11-15` or `15x22`
+fn play(game: &mut Game, notation: &str) -> Result<bool, String> {
+    let squares: Vec<Position> = notation
+        .split(['-', 'x'])
+        .map(|n| Position::from_square(n.parse().unwrap()).unwrap())
+        .collect();
+    let m = game
+        .board
+        .build_move(squares[0], &squares[1..], game.player, game.variant)?;
+    game.do_move(&m)
+}
+
+#[test]
+fn opening_exchange() {
+    let mut g = Game::new();
+    for m in ["11-15", "22-18", "15x22", "25x18"].iter() {
+        assert_eq!(play(&mut g, m), Ok(true));
+    }
+    let history: Vec<String> = g.history.iter().map(|m| m.to_string()).collect();
+    assert_eq!(history, vec!["11-15", "22-18", "15x22", "25x18"]);
+    assert_eq!(g.player, Player::Black);
+    assert_eq!(g.board.count_pieces(Player::Black), 11);
+    assert_eq!(g.board.count_pieces(Player::White), 11);
+}
+
+#[test]
+fn capture_is_mandatory() {
+    let mut g = Game::new();
+    play(&mut g, "11-15").unwrap();
+    play(&mut g, "22-18").unwrap();
+    assert_eq!(
+        play(&mut g, "9-13"),
+        Err(String::from("A capture is available and must be taken."))
+    );
+}
+
+#[test]
+fn out_of_turn() {
+    let mut g = Game::new();
+    assert!(play(&mut g, "22-18").is_err());
+    assert!(g.history.is_empty());
+}
+
+#[test]
+fn undo_to_start() {
+    let mut g = Game::new();
+    for m in ["11-15", "22-18", "15x22"].iter() {
+        play(&mut g, m).unwrap();
+    }
+    while g.undo().is_some() {}
+    assert_eq!(g.board, Board::new());
+    assert_eq!(g.player, Player::Black);
+}
@@ -0,0 +1,859 @@
+use rustedcheckers::clock::TimeControl;
+use rustedcheckers::game::Game;
+use rustedcheckers::networking::client::{Connection, Link};
+use rustedcheckers::networking::peer::Peer;
+use rustedcheckers::networking::protocol::{self, Message, OpenGame, Rejection, Token, VERSION};
+use rustedcheckers::networking::server;
+use rustedcheckers::networking::server::{AwayClock, Config};
+use rustedcheckers::player::Player;
+
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || server::serve(listener));
+    addr
+}
+
+fn start_server_with(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || server::serve_with(listener, config));
+    addr
+}
+
+/// Connects and registers a nickname, returning the connection and the
+/// games open in the lobby
+fn register(addr: SocketAddr, name: &str) -> (TcpStream, Vec<OpenGame>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from(name),
+    };
+    protocol::send(&mut stream, &hello).unwrap();
+    match protocol::recv(&mut stream).unwrap() {
+        Message::Games { open, .. } => (stream, open),
+        m => panic!("Expected the open games, not {:?}", m),
+    }
+}
+
+/// Connects and asks for a quick match
+fn join(addr: SocketAddr, name: &str) -> TcpStream {
+    let (mut stream, _) = register(addr, name);
+    protocol::send(&mut stream, &Message::QuickMatch).unwrap();
+    stream
+}
+
+/// Connects and asks for a quick match, returning once the server is
+/// waiting for someone to pair it with. Whoever joins next plays white.
+fn queue(addr: SocketAddr, name: &str) -> TcpStream {
+    let mut stream = join(addr, name);
+    protocol::send(&mut stream, &Message::ListGames).unwrap();
+    match protocol::recv(&mut stream).unwrap() {
+        Message::Games { .. } => stream,
+        m => panic!("Expected the open games, not {:?}", m),
+    }
+}
+
+/// Reads the state sent by the server after seating or a turn
+fn recv_state(stream: &mut TcpStream) -> Game {
+    match protocol::recv(stream).unwrap() {
+        Message::State(game) => *game,
+        m => panic!("Expected the game state, not {:?}", m),
+    }
+}
+
+/// Reads the session token sent after a seat is assigned
+fn recv_session(stream: &mut TcpStream) -> Token {
+    match protocol::recv(stream).unwrap() {
+        Message::Session(token) => token,
+        m => panic!("Expected a session token, not {:?}", m),
+    }
+}
+
+/// Seats two players, returning their connections
+fn seat(addr: SocketAddr) -> (TcpStream, TcpStream) {
+    let mut black = queue(addr, "Derek");
+    let mut white = join(addr, "David");
+    protocol::recv(&mut black).unwrap();
+    protocol::recv(&mut white).unwrap();
+    recv_session(&mut black);
+    recv_session(&mut white);
+    recv_state(&mut black);
+    recv_state(&mut white);
+    (black, white)
+}
+
+#[test]
+fn play_over_tcp() {
+    let addr = start_server();
+    let mut black = queue(addr, "Eric");
+    let mut white = join(addr, "Daniel");
+
+    assert_eq!(
+        protocol::recv(&mut black).unwrap(),
+        Message::SeatAssigned {
+            player: Player::Black,
+            opponent: String::from("Daniel"),
+        }
+    );
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::SeatAssigned {
+            player: Player::White,
+            opponent: String::from("Eric"),
+        }
+    );
+    assert_ne!(recv_session(&mut black), recv_session(&mut white));
+    let state = recv_state(&mut black);
+    assert_eq!(state.board, Game::new().board);
+    assert_eq!(state.name(Player::Black), "Eric");
+    assert_eq!(state.name(Player::White), "Daniel");
+    assert_eq!(recv_state(&mut white), state);
+
+    let m = Game::new().parse_move("11-15").unwrap();
+    protocol::send(&mut white, &Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::MoveRejected(Rejection::NotYourTurn)
+    );
+
+    protocol::send(&mut black, &Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut black).unwrap(),
+        Message::MoveAccepted(m.clone())
+    );
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::MoveAccepted(m)
+    );
+    let state = recv_state(&mut black);
+    assert_eq!(state.player, Player::White);
+    assert_eq!(state.history.len(), 1);
+    assert_eq!(recv_state(&mut white), state);
+
+    protocol::send(&mut white, &Message::Resign).unwrap();
+    let over = Message::GameOver {
+        winner: Some(Player::Black),
+        reason: String::from("Daniel resigned."),
+    };
+    assert_eq!(protocol::recv(&mut black).unwrap(), over);
+    assert_eq!(protocol::recv(&mut white).unwrap(), over);
+}
+
+#[test]
+fn illegal_move() {
+    let (mut black, _white) = seat(start_server());
+
+    let mut m = Game::new().parse_move("11-15").unwrap();
+    m.promoted = true;
+    protocol::send(&mut black, &Message::MoveRequest(m)).unwrap();
+    match protocol::recv(&mut black).unwrap() {
+        Message::MoveRejected(Rejection::Illegal(_)) => (),
+        m => panic!("Unexpected reply: {:?}", m),
+    }
+}
+
+#[test]
+fn draw_by_agreement() {
+    let (mut black, mut white) = seat(start_server());
+
+    protocol::send(&mut black, &Message::DrawOffer).unwrap();
+    assert_eq!(protocol::recv(&mut white).unwrap(), Message::DrawOffer);
+    protocol::send(&mut white, &Message::DrawOffer).unwrap();
+    let over = Message::GameOver {
+        winner: None,
+        reason: String::from("Draw agreed."),
+    };
+    assert_eq!(protocol::recv(&mut black).unwrap(), over);
+    assert_eq!(protocol::recv(&mut white).unwrap(), over);
+}
+
+#[test]
+fn version_mismatch() {
+    let addr = start_server();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let hello = Message::Hello {
+        version: VERSION + 1,
+        name: String::from("Future"),
+    };
+    protocol::send(&mut stream, &hello).unwrap();
+    match protocol::recv(&mut stream).unwrap() {
+        Message::GameOver { winner: None, .. } => (),
+        m => panic!("Unexpected reply: {:?}", m),
+    }
+}
+
+/// Waits up to a second for a message on a polled link
+fn poll<L: Link>(link: &mut L) -> Message {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        if let Some(m) = link.poll() {
+            return m.unwrap();
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("No message arrived");
+}
+
+#[test]
+fn polled_connection() {
+    let addr = start_server();
+    let mut black = Connection::open(addr, "Eric").unwrap();
+    match poll(&mut black) {
+        Message::Games { open, running } => assert!(open.is_empty() && running.is_empty()),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+    black.send(&Message::QuickMatch).unwrap();
+    black.send(&Message::ListGames).unwrap();
+    match poll(&mut black) {
+        Message::Games { open, running } => assert!(open.is_empty() && running.is_empty()),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+    assert!(black.poll().is_none());
+    let _white = join(addr, "Daniel");
+
+    match poll(&mut black) {
+        Message::SeatAssigned { player, .. } => assert_eq!(player, Player::Black),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+    match poll(&mut black) {
+        Message::Session(_) => (),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+    match poll(&mut black) {
+        Message::State(game) => assert_eq!(game.name(Player::White), "Daniel"),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
+
+#[test]
+fn nickname_taken() {
+    let addr = start_server();
+    let _first = register(addr, "Eric");
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from("Eric"),
+    };
+    protocol::send(&mut stream, &hello).unwrap();
+    assert_eq!(
+        protocol::recv(&mut stream).unwrap(),
+        Message::Refused(String::from("The nickname Eric is taken."))
+    );
+
+    // Control characters are dropped from names, as they are from chat
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from("Er\u{1b}ic"),
+    };
+    protocol::send(&mut stream, &hello).unwrap();
+    assert_eq!(
+        protocol::recv(&mut stream).unwrap(),
+        Message::Refused(String::from("The nickname Eric is taken."))
+    );
+}
+
+#[test]
+fn create_and_join() {
+    let addr = start_server();
+    let (mut host, _) = register(addr, "Eric");
+    protocol::send(&mut host, &Message::CreateGame(Some(Player::White))).unwrap();
+    let id = match protocol::recv(&mut host).unwrap() {
+        Message::GameCreated(id) => id,
+        m => panic!("Unexpected reply: {:?}", m),
+    };
+
+    let (mut guest, games) = register(addr, "Daniel");
+    assert_eq!(
+        games,
+        vec![OpenGame {
+            id,
+            host: String::from("Eric"),
+            color: Some(Player::White),
+        }]
+    );
+    protocol::send(&mut guest, &Message::JoinGame(id + 1)).unwrap();
+    match protocol::recv(&mut guest).unwrap() {
+        Message::Refused(_) => (),
+        m => panic!("Unexpected reply: {:?}", m),
+    }
+    match protocol::recv(&mut guest).unwrap() {
+        Message::Games { open, .. } => assert_eq!(open, games),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+
+    protocol::send(&mut guest, &Message::JoinGame(id)).unwrap();
+    assert_eq!(
+        protocol::recv(&mut guest).unwrap(),
+        Message::SeatAssigned {
+            player: Player::Black,
+            opponent: String::from("Eric"),
+        }
+    );
+    assert_eq!(
+        protocol::recv(&mut host).unwrap(),
+        Message::SeatAssigned {
+            player: Player::White,
+            opponent: String::from("Daniel"),
+        }
+    );
+
+    let (_, games) = register(addr, "Derek");
+    assert!(games.is_empty());
+}
+
+#[test]
+fn concurrent_games() {
+    let addr = start_server();
+    let (mut black1, mut white1) = seat(addr);
+    let mut black2 = queue(addr, "Eric");
+    let mut white2 = join(addr, "Daniel");
+    for stream in [&mut black2, &mut white2].iter_mut() {
+        protocol::recv(stream).unwrap();
+        recv_session(stream);
+        recv_state(stream);
+    }
+
+    // A turn in one game leaves the other alone
+    let m = Game::new().parse_move("11-15").unwrap();
+    protocol::send(&mut black1, &Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut black1).unwrap(),
+        Message::MoveAccepted(m)
+    );
+    assert_eq!(recv_state(&mut black1).history.len(), 1);
+
+    protocol::send(&mut white2, &Message::Resign).unwrap();
+    let over = Message::GameOver {
+        winner: Some(Player::Black),
+        reason: String::from("Daniel resigned."),
+    };
+    assert_eq!(protocol::recv(&mut black2).unwrap(), over);
+
+    protocol::recv(&mut white1).unwrap();
+    assert_eq!(recv_state(&mut white1).history.len(), 1);
+}
+
+#[test]
+fn spectate() {
+    let addr = start_server();
+    let (mut black, mut white) = seat(addr);
+    let m = Game::new().parse_move("11-15").unwrap();
+    protocol::send(&mut black, &Message::MoveRequest(m.clone())).unwrap();
+    protocol::recv(&mut black).unwrap();
+    recv_state(&mut black);
+
+    let (mut spectator, _) = register(addr, "Eric");
+    protocol::send(&mut spectator, &Message::ListGames).unwrap();
+    let id = match protocol::recv(&mut spectator).unwrap() {
+        Message::Games { running, .. } => {
+            assert_eq!(running.len(), 1);
+            assert_eq!(running[0].black, "Derek");
+            running[0].id
+        }
+        m => panic!("Unexpected message: {:?}", m),
+    };
+    protocol::send(&mut spectator, &Message::Watch(id)).unwrap();
+    assert_eq!(
+        protocol::recv(&mut spectator).unwrap(),
+        Message::Watching {
+            black: String::from("Derek"),
+            white: String::from("David"),
+        }
+    );
+    let state = recv_state(&mut spectator);
+    assert_eq!(state.history, vec![m]);
+
+    // Spectators may only watch
+    let reply = state.parse_move("22-18").unwrap();
+    protocol::send(&mut spectator, &Message::MoveRequest(reply.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut spectator).unwrap(),
+        Message::MoveRejected(Rejection::NotSeated)
+    );
+
+    protocol::recv(&mut white).unwrap();
+    recv_state(&mut white);
+    protocol::send(&mut white, &Message::MoveRequest(reply.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut spectator).unwrap(),
+        Message::MoveAccepted(reply)
+    );
+    assert_eq!(recv_state(&mut spectator).history.len(), 2);
+
+    protocol::send(&mut black, &Message::Resign).unwrap();
+    match protocol::recv(&mut spectator).unwrap() {
+        Message::GameOver { winner, .. } => assert_eq!(winner, Some(Player::White)),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
+
+#[test]
+fn chat() {
+    let addr = start_server();
+    let (mut black, mut white) = seat(addr);
+    let (mut spectator, _) = register(addr, "Dave");
+    protocol::send(&mut spectator, &Message::Watch(0)).unwrap();
+    protocol::recv(&mut spectator).unwrap();
+    recv_state(&mut spectator);
+
+    protocol::send(&mut white, &Message::Chat(String::from(" Good luck! "))).unwrap();
+    let said = Message::ChatMessage {
+        from: String::from("David"),
+        text: String::from("Good luck!"),
+    };
+    assert_eq!(protocol::recv(&mut black).unwrap(), said);
+    assert_eq!(protocol::recv(&mut white).unwrap(), said);
+    assert_eq!(protocol::recv(&mut spectator).unwrap(), said);
+
+    // Spectators may only listen
+    protocol::send(&mut spectator, &Message::Chat(String::from("Hi"))).unwrap();
+    match protocol::recv(&mut spectator).unwrap() {
+        Message::Refused(_) => (),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+
+    let long = "a".repeat(protocol::MAX_CHAT_LEN + 1);
+    protocol::send(&mut black, &Message::Chat(long)).unwrap();
+    match protocol::recv(&mut black).unwrap() {
+        Message::Refused(_) => (),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+
+    // Only a few messages may be sent at once
+    for _ in 0..5 {
+        protocol::send(&mut black, &Message::Chat(String::from("gg"))).unwrap();
+        protocol::recv(&mut black).unwrap();
+    }
+    protocol::send(&mut black, &Message::Chat(String::from("gg"))).unwrap();
+    assert_eq!(
+        protocol::recv(&mut black).unwrap(),
+        Message::Refused(String::from("You are chatting too quickly. Wait a moment."))
+    );
+}
+
+/// Seats two players on a server which gives them `grace` to come back,
+/// returning their connections and black's session token
+fn seat_with_grace(grace: Duration) -> (SocketAddr, TcpStream, TcpStream, Token) {
+    seat_with(Config {
+        grace,
+        ..Config::default()
+    })
+}
+
+/// Seats two players on a server configured by `config`, returning their
+/// connections and black's session token
+fn seat_with(config: Config) -> (SocketAddr, TcpStream, TcpStream, Token) {
+    let addr = start_server_with(config);
+
+    let mut black = queue(addr, "Derek");
+    let mut white = join(addr, "David");
+    protocol::recv(&mut black).unwrap();
+    protocol::recv(&mut white).unwrap();
+    let token = recv_session(&mut black);
+    recv_session(&mut white);
+    recv_state(&mut black);
+    recv_state(&mut white);
+    (addr, black, white, token)
+}
+
+#[test]
+fn reconnect() {
+    let (addr, black, mut white, token) = seat_with_grace(Duration::from_secs(60));
+    black.shutdown(Shutdown::Both).unwrap();
+    match protocol::recv(&mut white).unwrap() {
+        Message::PlayerAway { player, grace_ms } => {
+            assert_eq!(player, Player::Black);
+            assert_eq!(grace_ms, 60_000);
+        }
+        m => panic!("Unexpected message: {:?}", m),
+    }
+
+    let mut black = TcpStream::connect(addr).unwrap();
+    let resume = Message::Resume {
+        version: VERSION,
+        token,
+    };
+    protocol::send(&mut black, &resume).unwrap();
+    assert_eq!(
+        protocol::recv(&mut black).unwrap(),
+        Message::SeatAssigned {
+            player: Player::Black,
+            opponent: String::from("David"),
+        }
+    );
+    assert_eq!(recv_session(&mut black), token);
+    recv_state(&mut black);
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::PlayerBack(Player::Black)
+    );
+
+    // The game carries on where it left off
+    let m = Game::new().parse_move("11-15").unwrap();
+    protocol::send(&mut black, &Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::MoveAccepted(m)
+    );
+}
+
+#[test]
+fn grace_expires() {
+    let (addr, black, mut white, token) = seat_with_grace(Duration::from_millis(200));
+    drop(black);
+    match protocol::recv(&mut white).unwrap() {
+        Message::PlayerAway { .. } => (),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+    assert_eq!(
+        protocol::recv(&mut white).unwrap(),
+        Message::GameOver {
+            winner: Some(Player::White),
+            reason: String::from("Derek did not come back."),
+        }
+    );
+
+    // The session ends with the game
+    let mut black = TcpStream::connect(addr).unwrap();
+    let resume = Message::Resume {
+        version: VERSION,
+        token,
+    };
+    protocol::send(&mut black, &resume).unwrap();
+    match protocol::recv(&mut black).unwrap() {
+        Message::Refused(_) => (),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
+
+#[test]
+fn abandoned_game() {
+    let (addr, black, white, _) = seat_with_grace(Duration::from_millis(200));
+    let (mut spectator, _) = register(addr, "Dave");
+    protocol::send(&mut spectator, &Message::Watch(0)).unwrap();
+    protocol::recv(&mut spectator).unwrap();
+    recv_state(&mut spectator);
+
+    drop(black);
+    drop(white);
+    for _ in 0..2 {
+        match protocol::recv(&mut spectator).unwrap() {
+            Message::PlayerAway { .. } => (),
+            m => panic!("Unexpected message: {:?}", m),
+        }
+    }
+    assert_eq!(
+        protocol::recv(&mut spectator).unwrap(),
+        Message::GameOver {
+            winner: None,
+            reason: String::from("Neither player came back."),
+        }
+    );
+}
+
+#[test]
+fn silent_client_is_dropped() {
+    let addr = start_server_with(Config {
+        heartbeat: Duration::from_millis(50),
+        timeout: Duration::from_millis(300),
+        ..Config::default()
+    });
+
+    // Never reads, so never answers the server's pings
+    let mut silent = TcpStream::connect(addr).unwrap();
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from("Derek"),
+    };
+    protocol::send(&mut silent, &hello).unwrap();
+    protocol::send(&mut silent, &Message::QuickMatch).unwrap();
+
+    // Answers pings in the background
+    let mut white = Connection::open(addr, "David").unwrap();
+    white.send(&Message::QuickMatch).unwrap();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        match poll(&mut white) {
+            Message::PlayerAway { player, .. } => {
+                assert_eq!(player, Player::Black);
+                return;
+            }
+            Message::GameOver { reason, .. } => panic!("The game ended: {}", reason),
+            _ => (),
+        }
+    }
+    panic!("The silent client was never dropped");
+}
+
+#[test]
+fn client_which_stops_reading_is_dropped() {
+    let addr = start_server();
+    let mut deaf = TcpStream::connect(addr).unwrap();
+    let flood = thread::spawn(move || {
+        // Sends pings without ever reading the pongs, until the server
+        // gives up on it
+        (0..1_000_000).any(|n| protocol::send(&mut deaf, &Message::Ping(n)).is_err())
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    register(addr, "David");
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(flood.join().unwrap());
+}
+
+#[test]
+fn loss_on_time() {
+    let (_addr, mut black, mut white, _) = seat_with(Config {
+        time: Some(TimeControl::SuddenDeath(Duration::from_millis(500))),
+        ..Config::default()
+    });
+
+    let m = Game::new().parse_move("11-15").unwrap();
+    protocol::send(&mut black, &Message::MoveRequest(m)).unwrap();
+    protocol::recv(&mut white).unwrap();
+    let game = recv_state(&mut white);
+    let left = |p| game.time_left(p, Duration::from_secs(0)).unwrap();
+    assert!(left(Player::Black) < Duration::from_millis(500));
+    // White's clock started as the state was sent
+    assert!(left(Player::White) > Duration::from_millis(400));
+
+    match protocol::recv(&mut white).unwrap() {
+        Message::GameOver { winner, reason } => {
+            assert_eq!(winner, Some(Player::Black));
+            assert_eq!(reason, "David ran out of time.");
+        }
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
+
+#[test]
+fn clock_stops_while_away() {
+    let (addr, black, mut white, token) = seat_with(Config {
+        time: Some(TimeControl::SuddenDeath(Duration::from_millis(500))),
+        away_clock: AwayClock::Paused,
+        ..Config::default()
+    });
+    drop(black);
+    protocol::recv(&mut white).unwrap();
+    thread::sleep(Duration::from_millis(700));
+
+    let mut black = TcpStream::connect(addr).unwrap();
+    let resume = Message::Resume {
+        version: VERSION,
+        token,
+    };
+    protocol::send(&mut black, &resume).unwrap();
+    protocol::recv(&mut black).unwrap();
+    recv_session(&mut black);
+    let game = recv_state(&mut black);
+    assert!(game.time_left(Player::Black, Duration::from_secs(0)) > Some(Duration::from_secs(0)));
+}
+
+#[test]
+fn clock_stops_only_for_turn_missed() {
+    let (addr, mut black, mut white, token) = seat_with(Config {
+        time: Some(TimeControl::SuddenDeath(Duration::from_secs(1))),
+        away_clock: AwayClock::Paused,
+        ..Config::default()
+    });
+    let mut game = Game::new();
+    let m = game.parse_move("11-15").unwrap();
+    protocol::send(&mut black, &Message::MoveRequest(m.clone())).unwrap();
+    protocol::recv(&mut white).unwrap();
+    recv_state(&mut white);
+    drop(black);
+    protocol::recv(&mut white).unwrap();
+
+    // Black was away for a while before their turn began, which must not
+    // give them any more time
+    thread::sleep(Duration::from_millis(600));
+    game.do_move(&m).unwrap();
+    let m = game.parse_move("22-18").unwrap();
+    protocol::send(&mut white, &Message::MoveRequest(m)).unwrap();
+    protocol::recv(&mut white).unwrap();
+    recv_state(&mut white);
+
+    let mut black = TcpStream::connect(addr).unwrap();
+    let resume = Message::Resume {
+        version: VERSION,
+        token,
+    };
+    protocol::send(&mut black, &resume).unwrap();
+    let back = Instant::now();
+    loop {
+        if let Message::GameOver { winner, .. } = protocol::recv(&mut white).unwrap() {
+            assert_eq!(winner, Some(Player::White));
+            break;
+        }
+    }
+    assert!(back.elapsed() < Duration::from_millis(1400));
+}
+
+/// Checks a peer has been given its seat and the starting position
+fn expect_seat(peer: &mut Peer, seat: Player, opponent: &str) {
+    assert_eq!(
+        poll(peer),
+        Message::SeatAssigned {
+            player: seat,
+            opponent: String::from(opponent),
+        }
+    );
+    match poll(peer) {
+        Message::State(game) => assert_eq!(game.name(seat.switch()), opponent),
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
+
+#[test]
+fn peer_to_peer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let host = thread::spawn(move || Peer::host(&listener, "Eric", None, None).unwrap());
+    let mut white = Peer::join(addr, "Daniel", Some(Player::White)).unwrap();
+    let mut black = host.join().unwrap();
+
+    expect_seat(&mut black, Player::Black, "Daniel");
+    expect_seat(&mut white, Player::White, "Eric");
+
+    // Each peer checks its own turns before sending them
+    let m = Game::new().parse_move("11-15").unwrap();
+    white.send(&Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(
+        poll(&mut white),
+        Message::MoveRejected(Rejection::NotYourTurn)
+    );
+
+    black.send(&Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(poll(&mut white), Message::MoveAccepted(m.clone()));
+    assert_eq!(poll(&mut black), Message::MoveAccepted(m));
+    let (b, w) = match (poll(&mut black), poll(&mut white)) {
+        (Message::State(b), Message::State(w)) => (b, w),
+        m => panic!("Unexpected messages: {:?}", m),
+    };
+    assert_eq!(b, w);
+    assert_eq!(b.player, Player::White);
+
+    white
+        .send(&Message::Chat(String::from("Nice opening.")))
+        .unwrap();
+    let said = Message::ChatMessage {
+        from: String::from("Daniel"),
+        text: String::from("Nice opening."),
+    };
+    assert_eq!(poll(&mut white), said);
+    assert_eq!(poll(&mut black), said);
+
+    white.send(&Message::Resign).unwrap();
+    let over = Message::GameOver {
+        winner: Some(Player::Black),
+        reason: String::from("Daniel resigned."),
+    };
+    assert_eq!(poll(&mut white), over);
+    assert_eq!(poll(&mut black), over);
+}
+
+#[test]
+fn host_without_blocking() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    match Peer::host(&listener, "Eric", None, None) {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+        Ok(_) => panic!("Hosted a game no one joined"),
+    }
+
+    let guest = thread::spawn(move || Peer::join(addr, "Daniel", None).unwrap());
+    let start = Instant::now();
+    let mut black = loop {
+        match Peer::host(&listener, "Eric", None, None) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(10));
+            }
+            hosted => break hosted.unwrap(),
+        }
+    };
+    let mut white = guest.join().unwrap();
+    expect_seat(&mut black, Player::Black, "Daniel");
+    expect_seat(&mut white, Player::White, "Eric");
+}
+
+/// Hosts a game with a minute on the clock, joined by a guest which speaks
+/// the protocol by hand. Returns the host, playing black, and the guest.
+fn host_timed_game() -> (Peer, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let time = Some(TimeControl::SuddenDeath(Duration::from_secs(60)));
+    let host = thread::spawn(move || Peer::host(&listener, "Eric", None, time).unwrap());
+
+    let mut white = TcpStream::connect(addr).unwrap();
+    let hello = Message::Hello {
+        version: VERSION,
+        name: String::from("Daniel"),
+    };
+    protocol::send(&mut white, &hello).unwrap();
+    protocol::recv(&mut white).unwrap();
+    protocol::send(&mut white, &Message::ColorPreference(Some(Player::White))).unwrap();
+    protocol::recv(&mut white).unwrap();
+    recv_state(&mut white);
+    let mut black = host.join().unwrap();
+    expect_seat(&mut black, Player::Black, "Daniel");
+    (black, white)
+}
+
+#[test]
+fn peer_checks_claimed_result() {
+    let (mut black, mut white) = host_timed_game();
+
+    // Black has most of a minute left, so the claim is ignored
+    let over = Message::GameOver {
+        winner: Some(Player::White),
+        reason: String::from("Eric ran out of time."),
+    };
+    protocol::send(&mut white, &over).unwrap();
+    protocol::send(&mut white, &Message::Chat(String::from("Out of time?"))).unwrap();
+    assert_eq!(
+        poll(&mut black),
+        Message::ChatMessage {
+            from: String::from("Daniel"),
+            text: String::from("Out of time?"),
+        }
+    );
+}
+
+#[test]
+fn peer_checks_time_used() {
+    let (mut black, mut white) = host_timed_game();
+
+    let mut game = Game::new();
+    let m = game.parse_move("11-15").unwrap();
+    black.send(&Message::MoveRequest(m.clone())).unwrap();
+    protocol::recv(&mut white).unwrap();
+    protocol::recv(&mut white).unwrap();
+    protocol::send(&mut white, &Message::MoveAccepted(m.clone())).unwrap();
+    assert_eq!(poll(&mut black), Message::MoveAccepted(m.clone()));
+    poll(&mut black);
+
+    // White says a turn which took more than a second took no time at all
+    game.do_move(&m).unwrap();
+    let m = game.parse_move("22-18").unwrap();
+    thread::sleep(Duration::from_millis(1200));
+    protocol::send(&mut white, &Message::TimeUsed(0)).unwrap();
+    protocol::send(&mut white, &Message::MoveRequest(m.clone())).unwrap();
+    assert_eq!(poll(&mut black), Message::MoveAccepted(m));
+    match poll(&mut black) {
+        Message::State(game) => {
+            let left = game.time_left(Player::White, Duration::from_secs(0));
+            assert!(left < Some(Duration::from_millis(59_500)));
+        }
+        m => panic!("Unexpected message: {:?}", m),
+    }
+}
@@ -0,0 +1,31 @@
+use rustedcheckers::game::Game;
+use rustedcheckers::moves::Submove;
+use rustedcheckers::save;
+
+use std::env;
+use std::fs;
+
+#[test]
+fn resume_saved_game() {
+    let path = env::temp_dir().join(format!("rustedcheckers-it-{}.json", std::process::id()));
+    let mut g = Game::new();
+    g.do_submove(&Submove::new((5, 0), (4, 1))).unwrap();
+    save::save(&g, None, &path).unwrap();
+
+    let (mut resumed, computer) = save::load(&path).unwrap();
+    assert_eq!(resumed, g);
+    assert_eq!(computer, None);
+    assert_eq!(
+        resumed.undo().map(|m| m.to_string()),
+        Some(String::from("12-16"))
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn bincode_round_trip() {
+    let mut g = Game::new();
+    g.do_submove(&Submove::new((5, 2), (4, 3))).unwrap();
+    let bytes = bincode::serialize(&g).unwrap();
+    assert_eq!(bincode::deserialize::<Game>(&bytes).unwrap(), g);
+}